@@ -1,6 +1,12 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::{AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError};
+use bevy::sprite::{Material2d, Material2dKey};
 use std::{fs, io};
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+use crate::levels::{ColorChannelData, FadeData, Level, TriggerData};
 
 // Marker components for UI buttons
 #[derive(Component)]
@@ -13,6 +19,22 @@ pub struct BackButton;
 pub struct LevelButton {
     pub level_id: usize,
 }
+#[derive(Component)]
+pub struct StartPositionButton {
+    pub level_id: usize,
+    pub index: usize, // 0 is the beginning of the level, n is start position n - 1
+    pub count: usize,
+}
+
+// Title screen entry into the editor
+#[derive(Component)]
+pub struct EditorButton;
+
+// Opens a level in the editor, or a new empty level without an ID
+#[derive(Component)]
+pub struct EditorLevelButton {
+    pub level_id: Option<usize>,
+}
 
 // Marker components for cameras
 #[derive(Component)]
@@ -24,19 +46,297 @@ pub struct GameplayCamera;
 #[derive(Resource, Default)]
 pub struct SelectedLevel {
     pub level_id: usize,
+    pub start_position: Option<usize>,
+}
+
+// Level the editor opens, a new empty level when None
+#[derive(Resource, Default)]
+pub struct SelectedEditorLevel {
+    pub level_id: Option<usize>,
+}
+
+// Unsaved level the editor is playtesting, played instead of the selected level's file.
+// Death, Escape and the finish all return to the editor.
+#[derive(Resource)]
+pub struct PlaytestLevel {
+    pub level: Level,
+    pub start_x: Option<f32>, // Start at this x instead of the level start
 }
 
 #[derive(Component)]
 pub struct Player;
+
+// Sprite drawn for the player, a child so it can rotate and squash without moving the hitbox
+#[derive(Component)]
+pub struct PlayerVisual;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    #[default]
+    Cube,
+    // Holding jump longer jumps higher
+    Robot,
+    // Each press teleports to the opposite surface
+    Spider,
+    // Each press flips gravity, also mid-air
+    Swing,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [GameMode::Cube, GameMode::Robot, GameMode::Spider, GameMode::Swing];
+
+    // The mode after this one, for tools that cycle through them
+    pub fn next(self) -> Self {
+        let index = GameMode::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        GameMode::ALL[(index + 1) % GameMode::ALL.len()]
+    }
+
+    // Full-size hitbox of the mode
+    pub fn hitbox(self) -> Vec2 {
+        match self {
+            GameMode::Cube => Vec2::new(30.0, 30.0),
+            GameMode::Robot => Vec2::new(26.0, 30.0),
+            GameMode::Spider => Vec2::new(30.0, 22.0),
+            GameMode::Swing => Vec2::new(26.0, 26.0),
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            GameMode::Cube => Color::srgb(0.0, 0.0, 1.0),
+            GameMode::Robot => Color::srgb(1.0, 0.55, 0.0),
+            GameMode::Spider => Color::srgb(0.6, 0.1, 0.9),
+            GameMode::Swing => Color::srgb(0.1, 0.8, 0.4),
+        }
+    }
+}
+
+// Portal that switches the player to another game mode
+#[derive(Component)]
+pub struct GameModePortal(pub GameMode);
+
+// Orb that starts a dash when jump is pressed while touching it
+#[derive(Component)]
+pub struct DashOrb {
+    pub angle: f32, // Degrees
+    pub speed: Option<f32>,
+}
+
+// Pad that launches the player on contact
+#[derive(Component)]
+pub struct JumpPad {
+    pub velocity: f32,
+}
+
+// Teleport portal entrance, moving the player by the offset to its exit
+#[derive(Component)]
+pub struct TeleportPortal {
+    pub exit: Vec2,
+}
+
+// Sent when the player is teleported, so followers can jump along instead of sliding
+#[derive(Event)]
+pub struct PlayerTeleported {
+    pub offset: Vec2,
+}
+
+// Dash orbs the player currently overlaps
+#[derive(Component, Default)]
+pub struct OrbContact {
+    pub orbs: Vec<Entity>,
+}
+
+// Velocity of the dash in progress, if any
+#[derive(Component, Default)]
+pub struct Dash {
+    pub velocity: Option<Vec2>,
+}
+
+// Remaining time a held robot jump keeps rising
+#[derive(Component, Default)]
+pub struct RobotBoost {
+    pub remaining: f32,
+}
+
+// Fading trail left behind by a spider teleport
+#[derive(Component)]
+pub struct SpiderStreak {
+    pub timer: Timer,
+}
+
+// Movement settings the player currently runs with
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PlayerState {
+    pub game_mode: GameMode,
+    pub speed: f32, // Auto-scroll speed, or top speed in platformer mode
+    pub gravity_flipped: bool,
+    pub mini: bool,
+    pub platformer: bool,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            game_mode: GameMode::Cube,
+            speed: 200.0,
+            gravity_flipped: false,
+            mini: false,
+            platformer: false,
+        }
+    }
+}
+
+impl PlayerState {
+    pub fn size(&self) -> Vec2 {
+        let hitbox = self.game_mode.hitbox();
+        if self.mini { hitbox / 2.0 } else { hitbox }
+    }
+
+    pub fn gravity_scale(&self) -> f32 {
+        if self.gravity_flipped { -75.0 } else { 75.0 }
+    }
+
+    pub fn jump_velocity(&self) -> f32 {
+        let velocity = match self.game_mode {
+            // The robot starts lower and rises further while jump is held
+            GameMode::Robot => 220.0,
+            _ => 300.0,
+        };
+        let velocity = if self.mini { velocity * 0.8 } else { velocity };
+        if self.gravity_flipped { -velocity } else { velocity }
+    }
+}
 #[derive(Component)]
 pub struct Obstacle;
+// Object whose opacity follows the player's distance, through a material of its own
+#[derive(Component)]
+pub struct ProximityFade {
+    pub data: FadeData,
+    pub center: Vec2, // Center of the shape relative to the transform
+    pub channel: Option<u32>, // Channel whose color is followed, if any
+    pub color: Color, // Color used without a channel
+}
+
+// Invisible block that collision triggers watch for overlaps with other blocks
+#[derive(Component)]
+pub struct CollisionBlock {
+    pub id: u32,
+}
+
+// Spinning visual of a sawblade, the collider is on its parent
+#[derive(Component)]
+pub struct Sawblade {
+    pub rotation_speed: f32, // Degrees per second clockwise
+}
+
 #[derive(Component)]
 pub struct Floor;
 #[derive(Component)]
+pub struct GroundLine;
+#[derive(Component)]
 pub struct NonLethal;
+// Slope surface in world space, starting at the entity's translation
+#[derive(Component)]
+pub struct Slope {
+    pub width: f32,
+    pub rise: f32, // Negative for slopes that go down to the right
+}
+
+impl Slope {
+    // Height of the surface above the slope's origin at a local x, clamped to the slope
+    pub fn surface_height(&self, local_x: f32) -> f32 {
+        let t = (local_x / self.width).clamp(0.0, 1.0);
+        if self.rise >= 0.0 {
+            t * self.rise
+        } else {
+            (1.0 - t) * -self.rise
+        }
+    }
+}
+// Slope the player is currently running on, if any
+#[derive(Component, Default)]
+pub struct SlopeContact {
+    pub slope: Option<Entity>,
+}
+// Horizontal control in platformer levels, in pixels per second squared
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PlatformerSettings {
+    pub acceleration: f32,
+    pub deceleration: f32,
+}
+
+impl Default for PlatformerSettings {
+    fn default() -> Self {
+        Self {
+            acceleration: 1200.0,
+            deceleration: 1800.0,
+        }
+    }
+}
+
+// Jump timing windows, in milliseconds
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct JumpSettings {
+    pub buffer_ms: u64, // How long a press is remembered before landing
+    pub coyote_ms: u64, // How long after leaving the ground a press still jumps
+}
+
+impl Default for JumpSettings {
+    fn default() -> Self {
+        Self {
+            buffer_ms: 100,
+            coyote_ms: 80,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct JumpBuffer {
-    pub timer: Timer,
+    pub timer: Timer,  // Runs from the last jump press
+    pub coyote: Timer, // Runs from the last frame the player was grounded
+}
+
+impl JumpBuffer {
+    pub fn new(settings: &JumpSettings) -> Self {
+        Self {
+            timer: finished_timer(settings.buffer_ms),
+            coyote: finished_timer(settings.coyote_ms),
+        }
+    }
+
+    // Feeds one frame of input and returns whether the player should jump this frame
+    pub fn update(&mut self, delta: Duration, pressed: bool, grounded: bool) -> bool {
+        self.timer.tick(delta);
+        self.coyote.tick(delta);
+
+        if pressed {
+            self.timer.reset();
+        }
+        if grounded {
+            self.coyote.reset();
+        }
+
+        let buffered = !self.timer.finished();
+        let can_jump = grounded || !self.coyote.finished();
+        if buffered && can_jump {
+            // Consume both windows so one press gives exactly one jump
+            let buffer_window = self.timer.duration();
+            self.timer.tick(buffer_window);
+            let coyote_window = self.coyote.duration();
+            self.coyote.tick(coyote_window);
+            return true;
+        }
+        false
+    }
+}
+
+// A window that starts out already elapsed
+fn finished_timer(window_ms: u64) -> Timer {
+    let window = Duration::from_millis(window_ms);
+    let mut timer = Timer::new(window, TimerMode::Once);
+    timer.tick(window);
+    timer
 }
 #[derive(Component)]
 pub struct GameOverText;
@@ -48,14 +348,199 @@ pub struct ReturnToMenuButton;
 pub struct NextLevelButton;
 #[derive(Component)]
 pub struct FinishLine;
+#[derive(Component)]
+pub struct Coin {
+    pub index: usize,
+}
+
+// Object that move, rotate and scale triggers can animate
+#[derive(Component)]
+pub struct GroupMember {
+    pub groups: Vec<u32>,
+    pub base: Transform, // Transform the object was spawned with
+}
+
+#[derive(Component)]
+pub struct Trigger {
+    pub index: usize,
+    pub data: TriggerData,
+    pub fire_times: Vec<f32>, // Level clock times the trigger fired at, spawn triggers can fire it repeatedly
+}
+
+#[derive(Clone, Copy)]
+pub struct PendingSpawn {
+    pub time: f32,
+    pub group: u32,
+    pub order: usize, // Breaks ties between spawns due at the same time
+}
+
+// State of the trigger dispatch for the current run, reset on every restart
+#[derive(Resource, Default)]
+pub struct TriggerDispatch {
+    pub start_x: f32, // Position triggers behind this count as fired before the run started
+    pub pending: Vec<PendingSpawn>,
+    pub armed_counts: Vec<Entity>,
+    pub items: HashMap<u32, i32>,
+    pub next_order: usize,
+}
+
+pub struct ColorChannel {
+    pub base: Color, // Color at the start of the level
+    pub current: Color,
+    pub blending: bool,
+    pub material: Handle<ColorMaterial>,
+    pub blending_material: Handle<BlendingColorMaterial>,
+}
+
+// Shared materials for every color channel of the running level
+#[derive(Resource, Default)]
+pub struct ColorChannels {
+    pub channels: HashMap<u32, ColorChannel>,
+}
+
+impl ColorChannels {
+    pub fn add_channel(
+        &mut self,
+        data: &ColorChannelData,
+        materials: &mut Assets<ColorMaterial>,
+        blending_materials: &mut Assets<BlendingColorMaterial>,
+    ) {
+        let color = Color::srgba(data.color[0], data.color[1], data.color[2], data.alpha.unwrap_or(1.0));
+        self.channels.insert(data.id, ColorChannel {
+            base: color,
+            current: color,
+            blending: data.blending.unwrap_or(false),
+            material: materials.add(ColorMaterial::from(color)),
+            blending_material: blending_materials.add(BlendingColorMaterial { color: color.into() }),
+        });
+    }
+
+    pub fn color(&self, id: u32) -> Option<Color> {
+        self.channels.get(&id).map(|channel| channel.current)
+    }
+}
+
+// Flat color material drawn with additive blending, used by blending color channels
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct BlendingColorMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+}
+
+impl Material2d for BlendingColorMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/blending_color.wgsl".into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(target) = descriptor
+            .fragment
+            .as_mut()
+            .and_then(|fragment| fragment.targets.first_mut())
+            .and_then(|target| target.as_mut())
+        {
+            target.blend = Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Time since the run started, advanced only while playing
+#[derive(Resource, Default)]
+pub struct LevelClock {
+    pub elapsed: f32,
+}
+
+// Coins picked up during the current run, only kept if the level is completed
+#[derive(Resource, Default)]
+pub struct RunCoins {
+    pub collected: Vec<usize>,
+}
 
 #[derive(Resource, Default)]
 pub struct LevelProgress {
     pub current_percentage: f32,
+    pub from_start_position: bool, // Practice runs don't count toward progress records
+    pub platformer: bool, // Platformer levels are measured by completion and time
+    pub elapsed_time: f32,
 }
 
 #[derive(Component)]
 pub struct ProgressText;
+
+// Song playing during a level
+#[derive(Component)]
+pub struct LevelMusic;
+
+// Running end-of-level sequence, from touching the finish line until the victory screen
+#[derive(Resource)]
+pub struct LevelEnd {
+    pub timer: Timer,
+}
+
+impl LevelEnd {
+    // How long the player flies off before the victory screen shows
+    pub const DURATION: f32 = 1.5;
+}
+
+impl Default for LevelEnd {
+    fn default() -> Self {
+        Self { timer: Timer::from_seconds(Self::DURATION, TimerMode::Once) }
+    }
+}
+
+// Running death sequence, from the lethal contact until the restart or GameOver menu
+#[derive(Resource)]
+pub struct PlayerDeath {
+    pub timer: Timer,
+    pub camera_origin: Option<Vec3>, // Where the camera stood before shaking
+}
+
+impl PlayerDeath {
+    // How long the camera shakes
+    pub const SHAKE_TIME: f32 = 0.3;
+
+    pub fn new(settings: &DeathSettings) -> Self {
+        Self {
+            timer: Timer::from_seconds(settings.delay, TimerMode::Once),
+            camera_origin: None,
+        }
+    }
+}
+
+// What happens after the player dies
+#[derive(Resource)]
+pub struct DeathSettings {
+    pub delay: f32, // Seconds the death sequence plays
+    pub auto_restart: bool, // Restart the level instead of opening the GameOver menu
+}
+
+impl Default for DeathSettings {
+    fn default() -> Self {
+        Self {
+            delay: 1.0,
+            auto_restart: false,
+        }
+    }
+}
+
+// Short-lived sprite that drifts and fades out
+#[derive(Component)]
+pub struct Particle {
+    pub velocity: Vec2,
+    pub timer: Timer,
+}
 #[derive(Component)]
 pub struct DeathSound;
 #[derive(Component)]
@@ -69,8 +554,12 @@ pub struct GameAudio {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProgressEntry {
+    #[serde(default)]
+    pub level_id: Option<usize>, // Missing in histories written before entries were kept per level
     pub date: String,
     pub percentage: f32,
+    #[serde(default)]
+    pub time: Option<f32>, // Completion time of platformer levels, in seconds
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -79,6 +568,7 @@ pub struct ProgressHistory {
 }
 
 impl ProgressHistory {
+    pub const FILE_PATH: &'static str = "assets/progress.json";
 
     pub fn load(file_path: &str) -> Result<Self, io::Error> {
     match fs::read_to_string(file_path) {
@@ -98,10 +588,37 @@ impl ProgressHistory {
     }
 
     pub fn add_entry(&mut self, new_entry: ProgressEntry, file_path: &str) {
-        if let Some(existing_entry) = self.entries.iter_mut().find(|e| e.date == new_entry.date) {
+        self.merge(new_entry);
+        self.save(file_path);
+    }
+
+    // Untagged entries from older histories belong to no level, they only show in the full list
+    pub fn entries_for(&self, level_id: usize) -> impl Iterator<Item = &ProgressEntry> {
+        self.entries.iter().filter(move |e| e.level_id == Some(level_id))
+    }
+
+    pub fn best_time(&self, level_id: usize) -> Option<f32> {
+        self.entries_for(level_id)
+            .filter_map(|e| e.time)
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    // One entry per level and day, keeping the best percentage and time of that day
+    fn merge(&mut self, new_entry: ProgressEntry) {
+        if let Some(existing_entry) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.level_id == new_entry.level_id && e.date == new_entry.date)
+        {
             if new_entry.percentage > existing_entry.percentage {
                 existing_entry.percentage = new_entry.percentage;
             }
+            if let Some(time) = new_entry.time {
+                // Keep the fastest completion
+                if !existing_entry.time.is_some_and(|best| best <= time) {
+                    existing_entry.time = Some(time);
+                }
+            }
         } else {
             self.entries.push(new_entry);
         }
@@ -110,12 +627,180 @@ impl ProgressHistory {
             b.percentage
                 .partial_cmp(&a.percentage)
                 .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| match (a.time, b.time) {
+                    // Faster completions first, runs without a time after them
+                    (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => std::cmp::Ordering::Equal,
+                })
                 .then_with(|| b.date.cmp(&a.date))
         });
+    }
+}
+
+#[derive(Component)]
+pub struct LeaderboardButton;
+
+// Coins collected in completed runs, keyed by level id
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CoinRecord {
+    pub levels: HashMap<usize, Vec<usize>>,
+}
+
+impl CoinRecord {
+    pub const FILE_PATH: &'static str = "assets/coins.json";
+
+    pub fn load(file_path: &str) -> Result<Self, io::Error> {
+        let data = fs::read_to_string(file_path)?;
+        serde_json::from_str::<Self>(&data)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to parse JSON"))
+    }
+
+    pub fn save(&self, file_path: &str) {
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            let _ = fs::write(file_path, json);
+        }
+    }
+
+    pub fn collected(&self, level_id: usize) -> &[usize] {
+        self.levels.get(&level_id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn add_coins(&mut self, level_id: usize, coins: &[usize], file_path: &str) {
+        let collected = self.levels.entry(level_id).or_default();
+        for &coin in coins {
+            if !collected.contains(&coin) {
+                collected.push(coin);
+            }
+        }
+        collected.sort_unstable();
 
         self.save(file_path);
     }
 }
 
-#[derive(Component)]
-pub struct LeaderboardButton;
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    fn buffer() -> JumpBuffer {
+        JumpBuffer::new(&JumpSettings { buffer_ms: 100, coyote_ms: 80 })
+    }
+
+    #[test]
+    fn jumps_when_pressed_on_the_ground() {
+        let mut jump = buffer();
+        assert!(jump.update(FRAME, true, true));
+    }
+
+    #[test]
+    fn does_not_jump_in_the_air_without_coyote_time() {
+        let mut jump = buffer();
+        assert!(!jump.update(FRAME, true, false));
+    }
+
+    #[test]
+    fn press_shortly_before_landing_still_jumps() {
+        let mut jump = buffer();
+        assert!(!jump.update(FRAME, true, false));
+        for _ in 0..3 {
+            assert!(!jump.update(FRAME, false, false));
+        }
+        // 64ms after the press, inside the 100ms buffer
+        assert!(jump.update(FRAME, false, true));
+    }
+
+    #[test]
+    fn press_long_before_landing_is_forgotten() {
+        let mut jump = buffer();
+        assert!(!jump.update(FRAME, true, false));
+        for _ in 0..10 {
+            assert!(!jump.update(FRAME, false, false));
+        }
+        assert!(!jump.update(FRAME, false, true));
+    }
+
+    #[test]
+    fn press_shortly_after_leaving_a_ledge_still_jumps() {
+        let mut jump = buffer();
+        assert!(!jump.update(FRAME, false, true));
+        for _ in 0..3 {
+            assert!(!jump.update(FRAME, false, false));
+        }
+        // 64ms after the last grounded frame, inside the 80ms coyote window
+        assert!(jump.update(FRAME, true, false));
+    }
+
+    #[test]
+    fn press_long_after_leaving_a_ledge_does_not_jump() {
+        let mut jump = buffer();
+        assert!(!jump.update(FRAME, false, true));
+        for _ in 0..6 {
+            assert!(!jump.update(FRAME, false, false));
+        }
+        assert!(!jump.update(FRAME, true, false));
+    }
+
+    #[test]
+    fn one_press_gives_one_jump() {
+        let mut jump = buffer();
+        assert!(jump.update(FRAME, true, true));
+        // Still inside both windows, but the press was already used
+        assert!(!jump.update(FRAME, false, false));
+        assert!(!jump.update(FRAME, false, true));
+    }
+
+    #[test]
+    fn holding_jump_jumps_again_on_landing() {
+        let mut jump = buffer();
+        assert!(jump.update(FRAME, true, true));
+        for _ in 0..20 {
+            assert!(!jump.update(FRAME, true, false));
+        }
+        assert!(jump.update(FRAME, true, true));
+    }
+
+    fn entry(level_id: usize, date: &str, percentage: f32, time: Option<f32>) -> ProgressEntry {
+        ProgressEntry { level_id: Some(level_id), date: date.to_string(), percentage, time }
+    }
+
+    #[test]
+    fn progress_of_different_levels_on_the_same_day_is_kept_apart() {
+        let mut history = ProgressHistory::default();
+        history.merge(entry(1, "2024-05-01", 40.0, None));
+        history.merge(entry(2, "2024-05-01", 70.0, None));
+        history.merge(entry(1, "2024-05-01", 55.0, None));
+
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries_for(1).next().unwrap().percentage, 55.0);
+        assert_eq!(history.entries_for(2).next().unwrap().percentage, 70.0);
+    }
+
+    #[test]
+    fn keeps_the_fastest_time_per_level() {
+        let mut history = ProgressHistory::default();
+        history.merge(entry(3, "2024-05-01", 100.0, Some(42.0)));
+        history.merge(entry(3, "2024-05-01", 100.0, Some(45.0)));
+        history.merge(entry(3, "2024-05-02", 100.0, Some(39.5)));
+        history.merge(entry(4, "2024-05-02", 100.0, Some(12.0)));
+
+        assert_eq!(history.best_time(3), Some(39.5));
+        assert_eq!(history.entries_for(3).next().unwrap().date, "2024-05-02");
+        assert_eq!(history.best_time(5), None);
+    }
+
+    #[test]
+    fn entries_saved_before_level_tagging_are_kept() {
+        let mut history: ProgressHistory =
+            serde_json::from_str(r#"{ "entries": [{ "date": "2024-05-01", "percentage": 80.0 }] }"#).unwrap();
+        history.merge(entry(1, "2024-05-01", 30.0, None));
+
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries[0].level_id, None);
+        assert_eq!(history.entries[0].percentage, 80.0);
+        assert_eq!(history.entries_for(1).count(), 1);
+    }
+}
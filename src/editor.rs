@@ -1,38 +1,626 @@
 use bevy::prelude::*;
+use bevy::utils::HashSet;
+use std::collections::VecDeque;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::input::ButtonState;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 use std::fs;
+use std::path::Path;
 use bevy::window::{PrimaryWindow, Window};
-use crate::components::{Obstacle, Floor};
-use crate::levels::{Level, ObstacleData};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use crate::components::{Floor, GameMode, PlaytestLevel, SelectedEditorLevel};
+use crate::levels::{level_path, list_level_ids, load_level, parse_level_id, save_level, DashOrbData, Level, LevelObject, ObstacleData, TriggerAction, TriggerData, BACKGROUND_CHANNEL};
 use crate::states::GameState;
+use crate::systems::gameplay::{default_obstacle_color, obstacle_mesh, sawblade_mesh};
 
-#[derive(Default)]
-pub struct EditorPlugin;
+pub struct EditorPlugin {
+    pub history_depth: usize, // Undo steps kept before the oldest are forgotten
+}
+
+impl Default for EditorPlugin {
+    fn default() -> Self {
+        Self {
+            history_depth: EDITOR_HISTORY_DEPTH,
+        }
+    }
+}
 
 impl Plugin for EditorPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<EditorGrid>()
+            .init_resource::<EditorPalette>()
+            .init_resource::<EditorTool>()
+            .init_resource::<EditorSelection>()
+            .insert_resource(EditorHistory::with_depth(self.history_depth))
+            .init_non_send_resource::<EditorClipboard>()
+            .init_resource::<EditorMessage>()
+            .init_resource::<EditorAutosave>()
             .add_systems(OnEnter(GameState::Editor), setup_editor)
-            .add_systems(Update, editor_system.run_if(in_state(GameState::Editor)))
-            .add_systems(OnExit(GameState::Editor), cleanup_editor);
+            .add_systems(
+                Update,
+                (
+                    // Editing waits while a prompt takes the keyboard
+                    (
+                        editor_camera_system,
+                        editor_grid_settings_system,
+                        palette_button_system,
+                        editor_system.after(editor_camera_system).after(palette_button_system),
+                        editor_select_system.after(editor_system),
+                        editor_transform_system.after(editor_select_system),
+                        editor_property_system.after(editor_transform_system),
+                        editor_clipboard_system.after(editor_property_system),
+                        editor_history_system.after(editor_clipboard_system),
+                        editor_panel_system.after(editor_history_system),
+                        editor_playtest_system.after(editor_panel_system),
+                        editor_autosave_system.after(editor_playtest_system),
+                    )
+                        .run_if(not(resource_exists::<EditorPrompt>)),
+                    // Always runs and drops key presses while no prompt is open, so a prompt
+                    // never reads the key press that opened it
+                    editor_prompt_system.before(editor_panel_system),
+                    editor_grid_system.after(editor_camera_system).after(editor_grid_settings_system),
+                    editor_object_sync_system.after(editor_history_system),
+                    editor_selection_highlight_system.after(editor_object_sync_system),
+                    editor_position_text_system,
+                    editor_panel_text_system.after(editor_panel_system).after(editor_prompt_system),
+                )
+                    .run_if(in_state(GameState::Editor)),
+            )
+            .add_systems(OnExit(GameState::Editor), (editor_exit_autosave_system, cleanup_editor).chain());
     }
 }
 
 #[derive(Component)]
 pub struct EditorCamera;
 
-pub fn setup_editor(mut commands: Commands) {
+// UI shown on top of the editor
+#[derive(Component)]
+pub struct EditorUi;
+
+// Shows the camera and cursor x position, so long levels can be navigated
+#[derive(Component)]
+pub struct EditorPositionText;
+
+// Every kind of object the palette can place
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaletteItem {
+    #[default]
+    Block,
+    Spike,
+    HalfSpike,
+    Finish,
+    Pad,
+    Orb,
+    Portal,
+    Decoration,
+    Trigger,
+}
+
+impl PaletteItem {
+    pub const ALL: [PaletteItem; 9] = [
+        PaletteItem::Block,
+        PaletteItem::Spike,
+        PaletteItem::HalfSpike,
+        PaletteItem::Finish,
+        PaletteItem::Pad,
+        PaletteItem::Orb,
+        PaletteItem::Portal,
+        PaletteItem::Decoration,
+        PaletteItem::Trigger,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PaletteItem::Block => "Block",
+            PaletteItem::Spike => "Spike",
+            PaletteItem::HalfSpike => "Half Spike",
+            PaletteItem::Finish => "Finish",
+            PaletteItem::Pad => "Pad",
+            PaletteItem::Orb => "Orb",
+            PaletteItem::Portal => "Portal",
+            PaletteItem::Decoration => "Decoration",
+            PaletteItem::Trigger => "Trigger",
+        }
+    }
+
+    // The object the item places, at the origin
+    pub fn template(self) -> LevelObject {
+        let square = [[0.0, 0.0], [30.0, 0.0], [30.0, 30.0], [0.0, 30.0]];
+        let obstacle = match self {
+            PaletteItem::Block => ObstacleData {
+                vertices: square,
+                non_lethal: Some(true),
+                color: Some([0.35, 0.35, 0.45]),
+                ..default()
+            },
+            PaletteItem::Spike => ObstacleData {
+                vertices: [[0.0, 0.0], [30.0, 0.0], [15.0, 30.0], [0.0, 0.0]],
+                ..default()
+            },
+            PaletteItem::HalfSpike => ObstacleData {
+                vertices: [[0.0, 0.0], [30.0, 0.0], [15.0, 15.0], [0.0, 0.0]],
+                ..default()
+            },
+            PaletteItem::Finish => ObstacleData {
+                vertices: [[0.0, 0.0], [25.0, 0.0], [25.0, 500.0], [0.0, 500.0]],
+                is_finish: Some(true),
+                color: Some([0.0, 1.0, 0.0]),
+                ..default()
+            },
+            PaletteItem::Pad => ObstacleData {
+                vertices: [[0.0, 0.0], [30.0, 0.0], [30.0, 6.0], [0.0, 6.0]],
+                pad: Some(450.0),
+                ..default()
+            },
+            PaletteItem::Orb => ObstacleData {
+                vertices: [[15.0, 0.0], [30.0, 15.0], [15.0, 30.0], [0.0, 15.0]],
+                dash_orb: Some(DashOrbData { angle: 0.0, speed: None }),
+                ..default()
+            },
+            PaletteItem::Portal => ObstacleData {
+                vertices: [[0.0, 0.0], [20.0, 0.0], [20.0, 90.0], [0.0, 90.0]],
+                portal: Some(GameMode::Robot),
+                ..default()
+            },
+            PaletteItem::Decoration => ObstacleData {
+                vertices: square,
+                decoration: Some(true),
+                ..default()
+            },
+            PaletteItem::Trigger => {
+                return LevelObject::Trigger(TriggerData {
+                    position: Vec2::ZERO,
+                    action: TriggerAction::Move { group: 1, offset: Vec2::new(0.0, 30.0), duration: 0.5, easing: None },
+                    activation: None,
+                    groups: None,
+                    size: None,
+                    collision: None,
+                });
+            }
+        };
+        LevelObject::Obstacle(obstacle)
+    }
+}
+
+// Palette item that clicks place
+#[derive(Resource, Default)]
+pub struct EditorPalette {
+    pub selected: PaletteItem,
+}
+
+#[derive(Component)]
+pub struct PaletteButton(pub PaletteItem);
+
+// Camera and mesh that draw a palette preview into an image
+#[derive(Component)]
+pub struct PalettePreview;
+
+// Bar holding the palette buttons
+#[derive(Component)]
+pub struct PaletteBar;
+
+// What clicks on the level do. Tab switches tools, picking a palette item switches to placing.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EditorTool {
+    #[default]
+    Place,
+    Select,
+}
+
+impl EditorTool {
+    pub fn label(self) -> &'static str {
+        match self {
+            EditorTool::Place => "place",
+            EditorTool::Select => "select",
+        }
+    }
+}
+
+// Editor objects the transform keys act on
+#[derive(Resource, Default)]
+pub struct EditorSelection {
+    pub entities: HashSet<Entity>,
+}
+
+// Level settings the metadata panel shows, and the panel's save buttons
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorPanelButton {
+    LevelId,
+    Music,
+    Platformer,
+    Save,
+    SaveAs,
+    Playtest,
+    Back,
+}
+
+// Takes the keyboard from the editor until answered
+#[derive(Resource)]
+pub enum EditorPrompt {
+    // Typing into a metadata field. Enter keeps the value, Escape drops it.
+    Edit { field: EditorPanelButton, value: String, save_after: bool },
+    // Saving would replace a level file this session did not open or save
+    ConfirmOverwrite { level_id: usize },
+    // Work an earlier session never saved was found on entering the editor
+    Restore { recovery: Box<EditorRecovery> },
+    // Leaving the editor would drop changes that were never saved
+    ConfirmLeave,
+}
+
+// Contents of the recovery file
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EditorRecovery {
+    pub version: u32,
+    pub written_at: String,
+    pub saved_id: Option<usize>,
+    pub level: Level,
+}
+
+impl EditorRecovery {
+    // The recovery file, if there is one this version can read
+    pub fn load() -> Option<Self> {
+        let data = fs::read_to_string(EDITOR_RECOVERY_PATH).ok()?;
+        match serde_json::from_str::<EditorRecovery>(&data) {
+            Ok(recovery) if recovery.version == EDITOR_RECOVERY_VERSION => Some(recovery),
+            Ok(recovery) => {
+                warn!("Ignoring {}, it has version {} instead of {}.", EDITOR_RECOVERY_PATH, recovery.version, EDITOR_RECOVERY_VERSION);
+                None
+            }
+            Err(error) => {
+                warn!("Ignoring {}, it could not be read: {}", EDITOR_RECOVERY_PATH, error);
+                None
+            }
+        }
+    }
+
+    // Whether opening this level, or a new one for `None`, should offer the recovered work
+    pub fn offered_for(&self, opened_id: Option<usize>) -> bool {
+        self.saved_id == opened_id
+    }
+
+    // Removes the recovery file once its work is saved or declined
+    pub fn discard() {
+        if Path::new(EDITOR_RECOVERY_PATH).exists() {
+            if let Err(error) = fs::remove_file(EDITOR_RECOVERY_PATH) {
+                warn!("Failed to remove {}: {}", EDITOR_RECOVERY_PATH, error);
+            }
+        }
+    }
+}
+
+// Periodic autosave of the editor level
+#[derive(Resource)]
+pub struct EditorAutosave {
+    pub timer: Timer,
+    // The level as last loaded or saved, autosaves only happen when it differs
+    pub baseline: String,
+}
+
+impl Default for EditorAutosave {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(EDITOR_AUTOSAVE_INTERVAL, TimerMode::Repeating),
+            baseline: String::new(),
+        }
+    }
+}
+
+impl EditorAutosave {
+    // Whether the level differs from what was last loaded or saved
+    pub fn has_changes(&self, level: &Level) -> bool {
+        serde_json::to_string(level).ok().as_ref() != Some(&self.baseline)
+    }
+
+    // Writes the level to the recovery file if it has changed since it was loaded or saved
+    pub fn write(&mut self, editor_level: &EditorLevel, level: &Level) {
+        if !self.has_changes(level) {
+            return;
+        }
+        let recovery = EditorRecovery {
+            version: EDITOR_RECOVERY_VERSION,
+            written_at: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            saved_id: editor_level.saved_id,
+            level: level.clone(),
+        };
+        let serialized = serde_json::to_string_pretty(&recovery).expect("Failed to serialize recovery");
+        match fs::write(EDITOR_RECOVERY_PATH, serialized) {
+            Ok(()) => debug!("Autosaved level {} to {}.", level.level_id, EDITOR_RECOVERY_PATH),
+            Err(error) => warn!("Failed to autosave to {}: {}", EDITOR_RECOVERY_PATH, error),
+        }
+    }
+
+    // Marks a level as matching what is on disk
+    pub fn set_baseline(&mut self, level: &Level) {
+        self.baseline = serde_json::to_string(level).unwrap_or_default();
+    }
+}
+
+// Result of the last save, shown for a while
+#[derive(Resource, Default)]
+pub struct EditorMessage {
+    pub text: String,
+    pub timer: Timer,
+}
+
+impl EditorMessage {
+    pub fn show(&mut self, text: String) {
+        self.text = text;
+        self.timer = Timer::from_seconds(EDITOR_MESSAGE_TIME, TimerMode::Once);
+    }
+}
+
+// Shows the open prompt, or the last message
+#[derive(Component)]
+pub struct EditorNoticeText;
+
+// Render layer of the palette previews, kept out of the editor camera
+const PALETTE_PREVIEW_LAYER: usize = 1;
+// Size of the palette preview images, in pixels
+const PALETTE_PREVIEW_SIZE: u32 = 48;
+
+// Lines of the editor grid, rebuilt to cover whatever the camera sees
+#[derive(Component)]
+pub struct EditorGridLines;
+
+// How many lines each grid cell is split by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GridSubdivision {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+}
+
+impl GridSubdivision {
+    pub fn divisions(self) -> f32 {
+        match self {
+            GridSubdivision::Full => 1.0,
+            GridSubdivision::Half => 2.0,
+            GridSubdivision::Quarter => 4.0,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            GridSubdivision::Full => GridSubdivision::Half,
+            GridSubdivision::Half => GridSubdivision::Quarter,
+            GridSubdivision::Quarter => GridSubdivision::Full,
+        }
+    }
+}
+
+// Grid that placement and moves snap to. Holding Alt places freely.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct EditorGrid {
+    pub cell_size: f32, // One player block by default
+    pub subdivision: GridSubdivision,
+}
+
+impl Default for EditorGrid {
+    fn default() -> Self {
+        Self {
+            cell_size: 30.0,
+            subdivision: GridSubdivision::Full,
+        }
+    }
+}
+
+impl EditorGrid {
+    // Distance between snapping points
+    pub fn step(&self) -> f32 {
+        self.cell_size / self.subdivision.divisions()
+    }
+
+    pub fn snap(&self, position: Vec2) -> Vec2 {
+        let step = self.step();
+        (position / step).round() * step
+    }
+}
+
+// Whether the held keys turn snapping off
+pub fn snapping_disabled(keyboard_input: &ButtonInput<KeyCode>) -> bool {
+    keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight])
+}
+
+const EDITOR_MIN_CELL_SIZE: f32 = 7.5;
+const EDITOR_MAX_CELL_SIZE: f32 = 240.0;
+// Grid lines closer together on screen than this are left out
+const EDITOR_MIN_LINE_SPACING: f32 = 4.0;
+
+// Pan speed of the arrow keys at normal zoom, in pixels per second
+const EDITOR_PAN_SPEED: f32 = 600.0;
+const EDITOR_MIN_ZOOM: f32 = 0.25;
+const EDITOR_MAX_ZOOM: f32 = 4.0;
+
+// Rotation step with Alt held, in degrees. Without Alt objects turn by 90°.
+const EDITOR_FREE_ROTATION_STEP: f32 = 5.0;
+const EDITOR_SCALE_STEP: f32 = 2.0;
+const EDITOR_FREE_SCALE_STEP: f32 = 1.1;
+// How long save results stay on screen, in seconds
+const EDITOR_MESSAGE_TIME: f32 = 6.0;
+// Validation problems listed on screen after a save, the log has all of them
+const EDITOR_MESSAGE_PROBLEMS: usize = 5;
+
+// Seconds between autosaves of unsaved editor work
+const EDITOR_AUTOSAVE_INTERVAL: f32 = 30.0;
+// Unsaved editor work, written every autosave and when leaving the editor
+const EDITOR_RECOVERY_PATH: &str = "assets/editor_recovery.json";
+// Raised whenever `EditorRecovery` changes shape, older files are then left alone
+const EDITOR_RECOVERY_VERSION: u32 = 1;
+
+// Where copied objects go when there is no system clipboard, e.g. on a headless machine
+const EDITOR_CLIPBOARD_FALLBACK_PATH: &str = "editor_clipboard.json";
+
+// Cursor movement in pixels that turns a click into a drag
+const EDITOR_DRAG_THRESHOLD: f32 = 3.0;
+// Undo steps kept by default
+const EDITOR_HISTORY_DEPTH: usize = 200;
+
+// Level being edited. Its object lists stay empty, the objects live in `EditorObject`s.
+#[derive(Resource, Default, Clone)]
+pub struct EditorLevel {
+    pub level: Level,
+    pub next_object_id: u64,
+    pub saved_id: Option<usize>, // Level file this session opened or saved, replaced without asking
+}
+
+impl EditorLevel {
+    // The level with the given objects, in the order they were added so coin indices hold
+    pub fn with_objects<'a>(&self, objects: impl Iterator<Item = (&'a EditorObjectId, &'a EditorObject)>) -> Level {
+        let mut objects = objects.collect::<Vec<_>>();
+        objects.sort_by_key(|(id, _)| id.0);
+        let mut level = self.level.clone();
+        for (_, editor_object) in objects {
+            level.add_object(editor_object.object.clone());
+        }
+        level
+    }
+
+    pub fn new_object_id(&mut self) -> EditorObjectId {
+        self.next_object_id += 1;
+        EditorObjectId(self.next_object_id)
+    }
+}
+
+// Editor state kept while a playtest runs, restored when the editor opens again
+#[derive(Resource)]
+pub struct EditorSession {
+    pub editor_level: EditorLevel,
+    pub objects: Vec<(EditorObjectId, LevelObject)>,
+    pub camera: Transform,
+    pub zoom: f32,
+    pub selection: Vec<EditorObjectId>,
+}
+
+// Object placed in the editor, with everything it is saved with
+#[derive(Component)]
+pub struct EditorObject {
+    pub object: LevelObject,
+}
+
+// Names an editor object in the history. Undoing a delete spawns a new entity, the id stays.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EditorObjectId(pub u64);
+
+// Undoable change to the editor objects. Bulk operations are one command over many objects.
+#[derive(Clone)]
+pub enum EditorCommand {
+    // Objects placed or pasted
+    Add(Vec<(EditorObjectId, LevelObject)>),
+    // Objects deleted or cut
+    Remove(Vec<(EditorObjectId, LevelObject)>),
+    // Moves, transforms and property edits, as each object before and after
+    Edit(Vec<(EditorObjectId, LevelObject, LevelObject)>),
+}
+
+impl EditorCommand {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            EditorCommand::Add(objects) | EditorCommand::Remove(objects) => objects.is_empty(),
+            EditorCommand::Edit(edits) => edits.is_empty(),
+        }
+    }
+
+    // The command that takes this one back
+    pub fn inverse(self) -> Self {
+        match self {
+            EditorCommand::Add(objects) => EditorCommand::Remove(objects),
+            EditorCommand::Remove(objects) => EditorCommand::Add(objects),
+            EditorCommand::Edit(edits) => {
+                EditorCommand::Edit(edits.into_iter().map(|(id, before, after)| (id, after, before)).collect())
+            }
+        }
+    }
+}
+
+// Undo and redo stacks of the editor. Kept while the editor is left for a playtest.
+#[derive(Resource)]
+pub struct EditorHistory {
+    pub depth: usize, // Oldest steps are forgotten past this many
+    undo: VecDeque<EditorCommand>,
+    redo: Vec<EditorCommand>,
+}
+
+impl Default for EditorHistory {
+    fn default() -> Self {
+        Self::with_depth(EDITOR_HISTORY_DEPTH)
+    }
+}
+
+impl EditorHistory {
+    pub fn with_depth(depth: usize) -> Self {
+        Self {
+            depth,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    // Adds a command that was just carried out
+    pub fn record(&mut self, command: EditorCommand) {
+        if command.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push_back(command);
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    // The command that reverts the last step
+    fn undo(&mut self) -> Option<EditorCommand> {
+        let command = self.undo.pop_back()?;
+        self.redo.push(command.clone());
+        Some(command.inverse())
+    }
+
+    // The last undone step, to carry out again
+    fn redo(&mut self) -> Option<EditorCommand> {
+        let command = self.redo.pop()?;
+        self.undo.push_back(command.clone());
+        Some(command)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup_editor(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    selected_editor_level: Res<SelectedEditorLevel>,
+    session: Option<Res<EditorSession>>,
+    mut history: ResMut<EditorHistory>,
+    mut selection: ResMut<EditorSelection>,
+    mut autosave: ResMut<EditorAutosave>,
+) {
     // add camera
-    commands.spawn((
-        Camera2dBundle::default(),
-        EditorCamera,
-    ));
+    let mut camera = Camera2dBundle::default();
+    if let Some(session) = session.as_deref() {
+        camera.transform = session.camera;
+        camera.projection.scale = session.zoom;
+    }
+    commands.spawn((camera, EditorCamera));
 
     commands.spawn((
         SpriteBundle {
             sprite: Sprite {
                 color: Color::srgb(0.8, 0.8, 0.8),
-                custom_size: Some(Vec2::new(1000.0, 10.0)),
+                custom_size: Some(Vec2::new(100000.0, 10.0)),
                 ..default()
             },
             transform: Transform::from_xyz(0.0, -36.0, 0.0),
@@ -40,87 +628,1743 @@ pub fn setup_editor(mut commands: Commands) {
         },
         Floor,
     ));
+
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())).into(),
+            material: materials.add(ColorMaterial::from(Color::WHITE)),
+            transform: Transform::from_xyz(0.0, 0.0, -10.0),
+            ..default()
+        },
+        EditorGridLines,
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+        EditorUi,
+        EditorPositionText,
+    ));
+
+    // Back from a playtest, everything is as it was left
+    if let Some(session) = session.as_deref() {
+        commands.remove_resource::<EditorSession>();
+        commands.remove_resource::<PlaytestLevel>();
+        let editor_level = session.editor_level.clone();
+        for (id, object) in session.objects.iter() {
+            let entity = spawn_editor_object(&mut commands, &mut meshes, &mut materials, &editor_level.level, *id, object.clone());
+            if session.selection.contains(id) {
+                selection.entities.insert(entity);
+            }
+        }
+        finish_editor_setup(&mut commands, &asset_server, &mut meshes, &mut materials, &mut images, editor_level);
+        return;
+    }
+
+    // Open the selected level, or start a new one after the last level on disk
+    let (mut level, saved_id) = match selected_editor_level.level_id {
+        Some(level_id) => match load_level(level_id) {
+            Some(level) => (level, Some(level_id)),
+            None => {
+                warn!("Level {} could not be loaded. Opening it empty.", level_id);
+                (Level { level_id, ..default() }, None)
+            }
+        },
+        None => {
+            let level = Level {
+                level_id: list_level_ids().last().map_or(1, |last| last + 1),
+                ..default()
+            };
+            (level, None)
+        }
+    };
+    info!("Editing level {}.", level.level_id);
+    history.clear();
+    autosave.timer.reset();
+    autosave.set_baseline(&level);
+    // Work on another level stays in the recovery file until that level is opened
+    let recovery = EditorRecovery::load().filter(|recovery| recovery.offered_for(selected_editor_level.level_id));
+    if let Some(recovery) = recovery {
+        commands.insert_resource(EditorPrompt::Restore { recovery: Box::new(recovery) });
+    }
+
+    let objects = level.take_objects();
+    let mut editor_level = EditorLevel { level, next_object_id: 0, saved_id };
+    for object in objects {
+        let id = editor_level.new_object_id();
+        spawn_editor_object(&mut commands, &mut meshes, &mut materials, &editor_level.level, id, object);
+    }
+    finish_editor_setup(&mut commands, &asset_server, &mut meshes, &mut materials, &mut images, editor_level);
 }
 
-pub fn editor_system(
+// Background, palette and panel, once the level is known
+fn finish_editor_setup(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    images: &mut Assets<Image>,
+    editor_level: EditorLevel,
+) {
+    spawn_level_colors(commands, asset_server, meshes, materials, images, &editor_level.level);
+    spawn_metadata_panel(commands, asset_server);
+    commands.insert_resource(editor_level);
+}
+
+// Background and palette, both drawn with the level's color channels
+fn spawn_level_colors(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    images: &mut Assets<Image>,
+    level: &Level,
+) {
+    if let Some(background) = level.color_channels_with_defaults().iter().find(|channel| channel.id == BACKGROUND_CHANNEL) {
+        commands.insert_resource(ClearColor(Color::srgb(background.color[0], background.color[1], background.color[2])));
+    }
+    spawn_palette(commands, asset_server, meshes, materials, images, level);
+}
+
+pub fn spawn_editor_object(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    level: &Level,
+    id: EditorObjectId,
+    object: LevelObject,
+) -> Entity {
+    let (mesh, color) = object_visual(level, &object);
+    commands
+        .spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(mesh).into(),
+                material: materials.add(ColorMaterial::from(color)),
+                transform: Transform::from_translation(object.position().extend(0.0)),
+                ..default()
+            },
+            EditorObject { object },
+            id,
+        ))
+        .id()
+}
+
+// Mesh and color that show an object in the editor, matching gameplay where it is visible
+fn object_visual(level: &Level, object: &LevelObject) -> (Mesh, Color) {
+    match object {
+        LevelObject::Obstacle(data) => {
+            let color = if data.collision_block.is_some() {
+                // Invisible in game, but the editor has to show it
+                Color::srgba(0.6, 0.6, 0.6, 0.4)
+            } else if let Some(channel_id) = data.color_channel {
+                level.color_channels_with_defaults()
+                    .iter()
+                    .find(|channel| channel.id == channel_id)
+                    .map_or(Color::WHITE, |channel| {
+                        Color::srgba(channel.color[0], channel.color[1], channel.color[2], channel.alpha.unwrap_or(1.0))
+                    })
+            } else if let Some(color) = data.color {
+                Color::srgb(color[0], color[1], color[2])
+            } else {
+                default_obstacle_color(data)
+            };
+            (obstacle_mesh(data.shape()), color)
+        }
+        LevelObject::Coin(_) => (Circle::new(10.0).into(), Color::srgb(1.0, 0.85, 0.1)),
+        LevelObject::Sawblade(data) => {
+            let color = data.color.map_or(Color::srgb(0.7, 0.7, 0.75), |color| Color::srgb(color[0], color[1], color[2]));
+            (sawblade_mesh(data.radius), color)
+        }
+        LevelObject::Trigger(data) => {
+            let size = data.size.unwrap_or(Vec2::splat(30.0));
+            (Rectangle::from_size(size).into(), Color::srgba(0.2, 0.9, 1.0, 0.3))
+        }
+        LevelObject::StartPosition(_) => {
+            let arrow = Triangle2d::new(Vec2::new(-10.0, -12.0), Vec2::new(12.0, 0.0), Vec2::new(-10.0, 12.0));
+            (arrow.into(), Color::srgba(0.2, 1.0, 0.3, 0.7))
+        }
+    }
+}
+
+// World position under the cursor, through the camera's position and zoom
+pub fn cursor_world_position(window: &Window, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Vec2> {
+    let cursor_position = window.cursor_position()?;
+    camera.viewport_to_world_2d(camera_transform, cursor_position)
+}
+
+// Pans the editor camera with the arrow keys or by dragging with the middle mouse button,
+// and zooms it with the mouse wheel
+pub fn editor_camera_system(
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
-    windows: Query<&Window, With<PrimaryWindow>>,
-    mut commands: Commands,
-    mut query: Query<&Transform, With<Obstacle>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    time: Res<Time>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<EditorCamera>>,
 ) {
-    // Get the primary window
-    let window = windows.get_single().unwrap();
-
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        debug!("Mouse location x: {}, y: {}", window.cursor_position().unwrap().x, window.cursor_position().unwrap().y);
-        if let Some(cursor_position) = window.cursor_position() {
-            let world_position = Vec3::new(
-                cursor_position.x - window.width() / 2.0,
-                cursor_position.y - window.height() / 2.0,
-                0.0,
-            );
-
-            // Spawn a new obstacle at the cursor position
-            commands.spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        color: Color::srgb(1.0, 0.0, 0.0),
-                        custom_size: Some(Vec2::new(30.0, 30.0)),
-                        ..default()
-                    },
-                    transform: Transform::from_translation(world_position),
-                    ..default()
-                },
-                Obstacle,
-            ));
-        }
+    let Ok((mut camera_transform, mut projection)) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    let mut direction = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::ArrowLeft) {
+        direction.x -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowRight) {
+        direction.x += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowDown) {
+        direction.y -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowUp) {
+        direction.y += 1.0;
     }
+    let mut pan = direction * EDITOR_PAN_SPEED * projection.scale * time.delta_seconds();
 
-    if keyboard_input.just_pressed(KeyCode::KeyS) {
-        save_level(&query);
+    // Dragging moves the level with the cursor. Window y points down, world y points up.
+    let drag = mouse_motion.read().map(|motion| motion.delta).sum::<Vec2>();
+    if mouse_button_input.pressed(MouseButton::Middle) {
+        pan += Vec2::new(-drag.x, drag.y) * projection.scale;
+    }
+    camera_transform.translation += pan.extend(0.0);
+
+    for wheel in mouse_wheel.read() {
+        let steps = match wheel.unit {
+            MouseScrollUnit::Line => wheel.y,
+            MouseScrollUnit::Pixel => wheel.y / 100.0,
+        };
+        projection.scale = (projection.scale * 1.1_f32.powf(-steps)).clamp(EDITOR_MIN_ZOOM, EDITOR_MAX_ZOOM);
     }
 }
 
-fn save_level(query: &Query<&Transform, With<Obstacle>>) {
-    let mut obstacles = Vec::new();
+// G cycles the sub-grid, [ and ] halve or double the cell size
+pub fn editor_grid_settings_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut grid: ResMut<EditorGrid>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        grid.subdivision = grid.subdivision.next();
+        info!("Editor grid subdivision set to {:?}.", grid.subdivision);
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        grid.cell_size = (grid.cell_size / 2.0).max(EDITOR_MIN_CELL_SIZE);
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        grid.cell_size = (grid.cell_size * 2.0).min(EDITOR_MAX_CELL_SIZE);
+    }
+}
 
-    for transform in query.iter() {
-        obstacles.push(ObstacleData {
-            position: Vec2::new(transform.translation.x, transform.translation.y),
-            vertices: [
-                [0.0, 0.0],
-                [0.0, 30.0],
-                [30.0, 30.0],
-                [30.0, 0.0],
-            ],
-            non_lethal: Some(false),
-            is_finish: Some(false),
-            color: Some([1.0, 0.0, 0.0]),
-        })
+// Rebuilds the grid lines for the visible area when the camera or the grid changes
+pub fn editor_grid_system(
+    grid: Res<EditorGrid>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(Ref<Transform>, Ref<OrthographicProjection>), With<EditorCamera>>,
+    mut grid_query: Query<(&Handle<Mesh>, &mut Visibility), With<EditorGridLines>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let (Ok(window), Ok((camera_transform, projection)), Ok((mesh_handle, mut visibility))) =
+        (windows.get_single(), camera_query.get_single(), grid_query.get_single_mut())
+    else {
+        return;
+    };
+    let Some(mesh) = meshes.get_mut(mesh_handle) else {
+        return;
+    };
+    let first_build = mesh.count_vertices() == 0;
+    if !first_build && !grid.is_changed() && !camera_transform.is_changed() && !projection.is_changed() {
+        return;
     }
 
-    let level = Level {
-        level_id: 2,
-        obstacles,
+    let scale = projection.scale;
+    let center = camera_transform.translation.truncate();
+    let half_view = Vec2::new(window.width(), window.height()) / 2.0 * scale;
+    let (min, max) = (center - half_view, center + half_view);
+    let thickness = scale;
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    let mut add_quad = |from: Vec2, to: Vec2, color: [f32; 4]| {
+        let start = positions.len() as u32;
+        positions.extend([[from.x, from.y, 0.0], [to.x, from.y, 0.0], [to.x, to.y, 0.0], [from.x, to.y, 0.0]]);
+        colors.extend([color; 4]);
+        indices.extend([start, start + 1, start + 2, start + 2, start + 3, start]);
     };
 
-    let serialized_level = serde_json::to_string_pretty(&level).expect("Failed to serialize level");
-    fs::write("assets/levels/level_edited.json", serialized_level).expect("Failed to write level to file");
+    // Major lines on cell edges, fainter sub-grid lines in between
+    let step = grid.step();
+    let line_step = if step / scale >= EDITOR_MIN_LINE_SPACING { step } else { grid.cell_size };
+    if grid.cell_size / scale >= EDITOR_MIN_LINE_SPACING {
+        let divisions = (grid.cell_size / line_step).round() as i64;
+        let first_x = (min.x / line_step).floor() as i64;
+        let last_x = (max.x / line_step).ceil() as i64;
+        for index in first_x..=last_x {
+            let x = index as f32 * line_step;
+            let color = if index % divisions == 0 { [1.0, 1.0, 1.0, 0.25] } else { [1.0, 1.0, 1.0, 0.08] };
+            add_quad(Vec2::new(x - thickness / 2.0, min.y), Vec2::new(x + thickness / 2.0, max.y), color);
+        }
+        let first_y = (min.y / line_step).floor() as i64;
+        let last_y = (max.y / line_step).ceil() as i64;
+        for index in first_y..=last_y {
+            let y = index as f32 * line_step;
+            let color = if index % divisions == 0 { [1.0, 1.0, 1.0, 0.25] } else { [1.0, 1.0, 1.0, 0.08] };
+            add_quad(Vec2::new(min.x, y - thickness / 2.0), Vec2::new(max.x, y + thickness / 2.0), color);
+        }
+    }
+
+    // Zoomed out too far to show any lines
+    *visibility = if indices.is_empty() { Visibility::Hidden } else { Visibility::Inherited };
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_indices(Indices::U32(indices));
 }
 
-pub fn cleanup_editor(
-    mut commands: Commands,
-    editor_entities: Query<Entity, With<EditorCamera>>,
-    obstacle_entities: Query<Entity, With<Obstacle>>,
+pub fn editor_position_text_system(
+    grid: Res<EditorGrid>,
+    tool: Res<EditorTool>,
+    selection: Res<EditorSelection>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    mut text_query: Query<&mut Text, With<EditorPositionText>>,
 ) {
-    // Cleanup editor camera
-    for entity in editor_entities.iter() {
-        commands.entity(entity).despawn_recursive();
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_query.get_single()) else {
+        return;
+    };
+    let camera_x = camera_transform.translation().x;
+    let position = match cursor_world_position(window, camera, camera_transform) {
+        Some(cursor) => format!("x: {:.0}  cursor: {:.0}, {:.0}", camera_x, cursor.x, cursor.y),
+        None => format!("x: {:.0}", camera_x),
+    };
+    let mut label = format!("{}  grid: {} 1/{}  tool: {}", position, grid.cell_size, grid.subdivision.divisions(), tool.label());
+    if !selection.entities.is_empty() {
+        label += &format!("  selected: {}", selection.entities.len());
     }
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
 
-    // Cleanup obstacles
-    for entity in obstacle_entities.iter() {
-        commands.entity(entity).despawn_recursive();
+// Selects palette items by clicking them or with the number keys
+pub fn palette_button_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut palette: ResMut<EditorPalette>,
+    mut tool: ResMut<EditorTool>,
+    mut button_query: Query<(&Interaction, &PaletteButton, &mut BackgroundColor)>,
+) {
+    const NUMBER_KEYS: [KeyCode; 9] = [
+        KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5,
+        KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    for (key, item) in NUMBER_KEYS.iter().zip(PaletteItem::ALL) {
+        if keyboard_input.just_pressed(*key) {
+            palette.selected = item;
+            *tool = EditorTool::Place;
+        }
+    }
+
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            palette.selected = button.0;
+            *tool = EditorTool::Place;
+        }
+        *color = if button.0 == palette.selected && *tool == EditorTool::Place {
+            BackgroundColor(Color::srgb(0.85, 0.85, 0.85))
+        } else if *interaction == Interaction::Hovered {
+            BackgroundColor(Color::srgb(0.75, 0.75, 0.75))
+        } else {
+            BackgroundColor(Color::srgb(0.5, 0.5, 0.5))
+        };
+    }
+}
+
+// Area an object covers, relative to its position
+pub fn object_bounds(object: &LevelObject) -> Rect {
+    match object {
+        LevelObject::Obstacle(data) => {
+            let shape = data.shape();
+            let points = shape.iter().map(|&[x, y]| Vec2::new(x, y));
+            let min = points.clone().fold(Vec2::splat(f32::MAX), Vec2::min);
+            let max = points.fold(Vec2::splat(f32::MIN), Vec2::max);
+            Rect::from_corners(min, max)
+        }
+        LevelObject::Coin(_) => Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(10.0)),
+        LevelObject::Sawblade(data) => Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(data.radius)),
+        LevelObject::Trigger(data) => Rect::from_center_size(Vec2::ZERO, data.size.unwrap_or(Vec2::splat(30.0))),
+        LevelObject::StartPosition(_) => Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(12.0)),
+    }
+}
+
+// Bar of palette buttons along the bottom. Each preview is drawn by its own camera into an
+// image, from a copy of the object placed far away on a render layer of its own.
+fn spawn_palette(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    images: &mut Assets<Image>,
+    level: &Level,
+) {
+    let preview_layer = RenderLayers::layer(PALETTE_PREVIEW_LAYER);
+    let mut previews = Vec::new();
+    for (index, item) in PaletteItem::ALL.into_iter().enumerate() {
+        let size = Extent3d {
+            width: PALETTE_PREVIEW_SIZE,
+            height: PALETTE_PREVIEW_SIZE,
+            ..default()
+        };
+        let mut image = Image {
+            texture_descriptor: TextureDescriptor {
+                label: None,
+                size,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Bgra8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        image.resize(size);
+        let image = images.add(image);
+
+        let object = item.template();
+        let bounds = object_bounds(&object);
+        let origin = Vec2::new(100000.0 + index as f32 * 1000.0, 100000.0);
+        let (mesh, color) = object_visual(level, &object);
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(mesh).into(),
+                material: materials.add(ColorMaterial::from(color)),
+                transform: Transform::from_translation((origin - bounds.center()).extend(0.0)),
+                ..default()
+            },
+            preview_layer.clone(),
+            PalettePreview,
+        ));
+
+        // Zoom out far enough for the whole object to fit, with a small margin
+        let mut camera = Camera2dBundle::default();
+        camera.camera.target = RenderTarget::Image(image.clone());
+        camera.camera.clear_color = ClearColorConfig::Custom(Color::NONE);
+        camera.camera.order = -1 - index as isize;
+        camera.projection.scale = (bounds.size().max_element() * 1.2 / PALETTE_PREVIEW_SIZE as f32).max(0.5);
+        camera.transform.translation = origin.extend(camera.transform.translation.z);
+        commands.spawn((camera, preview_layer.clone(), PalettePreview));
+
+        previews.push((item, image));
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Row,
+                    justify_content: JustifyContent::Center,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                ..default()
+            },
+            // Lets clicks on the bar itself be told apart from clicks on the level
+            Interaction::default(),
+            EditorUi,
+            PaletteBar,
+        ))
+        .with_children(|bar| {
+            for (item, image) in previews {
+                bar.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(80.0),
+                            height: Val::Px(76.0),
+                            margin: UiRect::all(Val::Px(4.0)),
+                            flex_direction: FlexDirection::Column,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                        ..default()
+                    },
+                    PaletteButton(item),
+                    EditorUi,
+                ))
+                .with_children(|button| {
+                    button.spawn(ImageBundle {
+                        style: Style {
+                            width: Val::Px(PALETTE_PREVIEW_SIZE as f32),
+                            height: Val::Px(PALETTE_PREVIEW_SIZE as f32),
+                            ..default()
+                        },
+                        image: UiImage::new(image),
+                        ..default()
+                    });
+                    button.spawn(TextBundle::from_section(
+                        item.label(),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 14.0,
+                            color: Color::BLACK,
+                        },
+                    ));
+                });
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn editor_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    ui_query: Query<&Interaction, With<EditorUi>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut editor_level: ResMut<EditorLevel>,
+    grid: Res<EditorGrid>,
+    palette: Res<EditorPalette>,
+    mut tool: ResMut<EditorTool>,
+    mut selection: ResMut<EditorSelection>,
+    mut history: ResMut<EditorHistory>,
+) {
+    // Get the primary window
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_query.get_single()) else {
+        return;
+    };
+
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        *tool = match *tool {
+            EditorTool::Place => EditorTool::Select,
+            EditorTool::Select => EditorTool::Place,
+        };
+    }
+
+    // Clicks on the editor UI are not placements
+    let over_ui = ui_query.iter().any(|interaction| *interaction != Interaction::None);
+    if *tool == EditorTool::Place && mouse_button_input.just_pressed(MouseButton::Left) && !over_ui {
+        if let Some(cursor_position) = cursor_world_position(window, camera, camera_transform) {
+            // Center the new object on the cursor, then snap its origin
+            let mut object = palette.selected.template();
+            let position = cursor_position - object_bounds(&object).center();
+            let position = if snapping_disabled(&keyboard_input) { position } else { grid.snap(position) };
+            debug!("Placing {:?} at x: {}, y: {}", palette.selected, position.x, position.y);
+            object.set_position(position);
+            // The new object is selected, so it can be adjusted right away
+            let id = editor_level.new_object_id();
+            history.record(EditorCommand::Add(vec![(id, object.clone())]));
+            let entity = spawn_editor_object(&mut commands, &mut meshes, &mut materials, &editor_level.level, id, object);
+            selection.entities.clear();
+            selection.entities.insert(entity);
+        }
+    }
+}
+
+// Panel in the top left with the level's settings and the save buttons
+fn spawn_metadata_panel(commands: &mut Commands, asset_server: &AssetServer) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                ..default()
+            },
+            Interaction::default(),
+            EditorUi,
+        ))
+        .with_children(|panel| {
+            for button in [
+                EditorPanelButton::LevelId,
+                EditorPanelButton::Music,
+                EditorPanelButton::Platformer,
+                EditorPanelButton::Save,
+                EditorPanelButton::SaveAs,
+                EditorPanelButton::Playtest,
+                EditorPanelButton::Back,
+            ] {
+                panel
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                min_width: Val::Px(220.0),
+                                margin: UiRect::all(Val::Px(2.0)),
+                                padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                                ..default()
+                            },
+                            background_color: BackgroundColor(Color::srgb(0.3, 0.3, 0.3)),
+                            ..default()
+                        },
+                        button,
+                        EditorUi,
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 18.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                    });
+            }
+        });
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 20.0,
+                color: Color::srgb(1.0, 0.9, 0.4),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(50.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+        EditorUi,
+        EditorNoticeText,
+    ));
+}
+
+// Writes the level to its file, asking first when that would replace a level file this
+// session did not open or save. Reports validation problems with the result.
+fn save_editor_level<'a>(
+    commands: &mut Commands,
+    editor_level: &mut EditorLevel,
+    objects: impl Iterator<Item = (&'a EditorObjectId, &'a EditorObject)>,
+    message: &mut EditorMessage,
+    autosave: &mut EditorAutosave,
+    overwrite: bool,
+) {
+    let level_id = editor_level.level.level_id;
+    if !overwrite && editor_level.saved_id != Some(level_id) && Path::new(&level_path(level_id)).exists() {
+        commands.insert_resource(EditorPrompt::ConfirmOverwrite { level_id });
+        return;
+    }
+
+    let level = editor_level.with_objects(objects);
+    if let Err(error) = save_level(&level) {
+        warn!("Failed to save level {}: {}", level_id, error);
+        message.show(format!("Could not save level {}: {}", level_id, error));
+        return;
+    }
+    editor_level.saved_id = Some(level_id);
+    info!("Saved level {} to {}.", level_id, level_path(level_id));
+    // The work is safe on disk, there is nothing left to recover
+    autosave.set_baseline(&level);
+    EditorRecovery::discard();
+
+    let problems = level.validate();
+    for problem in problems.iter() {
+        warn!("Level {}: {}", level_id, problem);
+    }
+    let mut text = match problems.len() {
+        0 => format!("Saved level {}.", level_id),
+        1 => format!("Saved level {} with 1 problem:", level_id),
+        count => format!("Saved level {} with {} problems:", level_id, count),
+    };
+    for problem in problems.iter().take(EDITOR_MESSAGE_PROBLEMS) {
+        text += &format!("\n{}", problem);
+    }
+    if problems.len() > EDITOR_MESSAGE_PROBLEMS {
+        text += &format!("\n...and {} more, see the log.", problems.len() - EDITOR_MESSAGE_PROBLEMS);
+    }
+    message.show(text);
+}
+
+// Ctrl+S saves, Ctrl+Shift+S asks for a level ID to save as. The metadata panel buttons
+// edit the level's settings or save it. Escape or Back returns to the level picker, asking
+// first when there are unsaved changes.
+#[allow(clippy::too_many_arguments)]
+pub fn editor_panel_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    button_query: Query<(&Interaction, &EditorPanelButton), Changed<Interaction>>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut message: ResMut<EditorMessage>,
+    mut autosave: ResMut<EditorAutosave>,
+    object_query: Query<(&EditorObjectId, &EditorObject)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut pressed = button_query
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, &button)| button)
+        .collect::<Vec<_>>();
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) && keyboard_input.just_pressed(KeyCode::KeyS) {
+        if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            pressed.push(EditorPanelButton::SaveAs);
+        } else {
+            pressed.push(EditorPanelButton::Save);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        pressed.push(EditorPanelButton::Back);
+    }
+
+    for button in pressed {
+        match button {
+            EditorPanelButton::LevelId | EditorPanelButton::SaveAs => {
+                commands.insert_resource(EditorPrompt::Edit {
+                    field: EditorPanelButton::LevelId,
+                    value: editor_level.level.level_id.to_string(),
+                    save_after: button == EditorPanelButton::SaveAs,
+                });
+            }
+            EditorPanelButton::Music => {
+                commands.insert_resource(EditorPrompt::Edit {
+                    field: EditorPanelButton::Music,
+                    value: editor_level.level.music.clone().unwrap_or_default(),
+                    save_after: false,
+                });
+            }
+            EditorPanelButton::Platformer => {
+                let platformer = !editor_level.level.platformer.unwrap_or(false);
+                editor_level.level.platformer = Some(platformer);
+            }
+            EditorPanelButton::Save => {
+                save_editor_level(&mut commands, &mut editor_level, object_query.iter(), &mut message, &mut autosave, false);
+            }
+            // Started by `editor_playtest_system`
+            EditorPanelButton::Playtest => {}
+            EditorPanelButton::Back => {
+                let level = editor_level.with_objects(object_query.iter());
+                if autosave.has_changes(&level) {
+                    commands.insert_resource(EditorPrompt::ConfirmLeave);
+                } else {
+                    next_state.set(GameState::EditorLevelSelection);
+                }
+                return;
+            }
+        }
+    }
+}
+
+// Types into the field being edited, or answers the overwrite, restore or leave question
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn editor_prompt_system(
+    mut commands: Commands,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    prompt: Option<ResMut<EditorPrompt>>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut editor_level: ResMut<EditorLevel>,
+    mut message: ResMut<EditorMessage>,
+    mut autosave: ResMut<EditorAutosave>,
+    mut history: ResMut<EditorHistory>,
+    mut selection: ResMut<EditorSelection>,
+    object_query: Query<(Entity, &EditorObjectId, &EditorObject)>,
+    palette_query: Query<Entity, Or<(With<PaletteBar>, With<PalettePreview>)>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(mut prompt) = prompt else {
+        // Otherwise a stale Enter or Y could answer the next prompt as soon as it opens
+        keyboard_events.clear();
+        return;
+    };
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match prompt.as_mut() {
+            EditorPrompt::Edit { field, value, save_after } => match &event.logical_key {
+                Key::Enter => {
+                    commands.remove_resource::<EditorPrompt>();
+                    match field {
+                        EditorPanelButton::LevelId => match parse_level_id(value) {
+                            Some(level_id) if level_id > 0 => editor_level.level.level_id = level_id,
+                            _ => {
+                                message.show(format!("{} is not a level ID or level file.", value));
+                                return;
+                            }
+                        },
+                        EditorPanelButton::Music => {
+                            let music = value.trim();
+                            editor_level.level.music = (!music.is_empty()).then(|| music.to_string());
+                        }
+                        _ => {}
+                    }
+                    if *save_after {
+                        let objects = object_query.iter().map(|(_, id, editor_object)| (id, editor_object));
+                        save_editor_level(&mut commands, &mut editor_level, objects, &mut message, &mut autosave, false);
+                    }
+                    return;
+                }
+                Key::Escape => {
+                    commands.remove_resource::<EditorPrompt>();
+                    return;
+                }
+                Key::Backspace => {
+                    value.pop();
+                }
+                Key::Space if *field == EditorPanelButton::Music => value.push(' '),
+                Key::Character(characters) => value.push_str(characters),
+                _ => {}
+            },
+            EditorPrompt::ConfirmOverwrite { .. } => {
+                if is_yes(&event.logical_key) {
+                    commands.remove_resource::<EditorPrompt>();
+                    let objects = object_query.iter().map(|(_, id, editor_object)| (id, editor_object));
+                    save_editor_level(&mut commands, &mut editor_level, objects, &mut message, &mut autosave, true);
+                    return;
+                }
+                if is_no(&event.logical_key) {
+                    commands.remove_resource::<EditorPrompt>();
+                    return;
+                }
+            }
+            EditorPrompt::Restore { recovery } => {
+                if is_yes(&event.logical_key) {
+                    commands.remove_resource::<EditorPrompt>();
+                    // The recovered level replaces whatever was opened
+                    for (entity, _, _) in object_query.iter() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                    history.clear();
+                    selection.entities.clear();
+                    let mut level = recovery.level.clone();
+                    let objects = level.take_objects();
+                    *editor_level = EditorLevel { level, next_object_id: 0, saved_id: recovery.saved_id };
+                    for object in objects {
+                        let id = editor_level.new_object_id();
+                        spawn_editor_object(&mut commands, &mut meshes, &mut materials, &editor_level.level, id, object);
+                    }
+                    // The recovered level can have other color channels
+                    for entity in palette_query.iter() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                    spawn_level_colors(&mut commands, &asset_server, &mut meshes, &mut materials, &mut images, &editor_level.level);
+                    info!("Restored unsaved work on level {}.", editor_level.level.level_id);
+                    message.show(format!("Restored unsaved work on level {}.", editor_level.level.level_id));
+                    return;
+                }
+                if is_no(&event.logical_key) {
+                    commands.remove_resource::<EditorPrompt>();
+                    EditorRecovery::discard();
+                    return;
+                }
+            }
+            EditorPrompt::ConfirmLeave => {
+                if is_yes(&event.logical_key) {
+                    commands.remove_resource::<EditorPrompt>();
+                    // The changes are dropped on purpose, so leaving must not autosave them
+                    let level = editor_level.with_objects(object_query.iter().map(|(_, id, editor_object)| (id, editor_object)));
+                    autosave.set_baseline(&level);
+                    EditorRecovery::discard();
+                    info!("Left the editor without saving level {}.", level.level_id);
+                    next_state.set(GameState::EditorLevelSelection);
+                    return;
+                }
+                if is_no(&event.logical_key) {
+                    commands.remove_resource::<EditorPrompt>();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// P plays the level as it is in the editor, Shift+P starts from the cursor's x. The editor
+// state is put aside in an `EditorSession` and restored when the playtest ends.
+#[allow(clippy::too_many_arguments)]
+pub fn editor_playtest_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    button_query: Query<(&Interaction, &EditorPanelButton), Changed<Interaction>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &Transform, &OrthographicProjection), With<EditorCamera>>,
+    editor_level: Res<EditorLevel>,
+    selection: Res<EditorSelection>,
+    object_query: Query<(Entity, &EditorObjectId, &EditorObject)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let button_pressed = button_query
+        .iter()
+        .any(|(interaction, button)| *interaction == Interaction::Pressed && *button == EditorPanelButton::Playtest);
+    let key_pressed = keyboard_input.just_pressed(KeyCode::KeyP)
+        && !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !button_pressed && !key_pressed {
+        return;
+    }
+    let Ok((camera, camera_global_transform, camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+
+    let from_cursor = key_pressed && keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let start_x = if from_cursor {
+        let Ok(window) = windows.get_single() else {
+            return;
+        };
+        cursor_world_position(window, camera, camera_global_transform).map(|cursor| cursor.x)
+    } else {
+        None
+    };
+
+    let level = editor_level.with_objects(object_query.iter().map(|(_, id, editor_object)| (id, editor_object)));
+    info!("Playtesting level {}.", level.level_id);
+    commands.insert_resource(PlaytestLevel { level, start_x });
+    commands.insert_resource(EditorSession {
+        editor_level: editor_level.clone(),
+        objects: object_query.iter().map(|(_, &id, editor_object)| (id, editor_object.object.clone())).collect(),
+        camera: *camera_transform,
+        zoom: projection.scale,
+        selection: object_query
+            .iter_many(selection.entities.iter())
+            .map(|(_, &id, _)| id)
+            .collect(),
+    });
+    next_state.set(GameState::Playing);
+}
+
+fn is_yes(key: &Key) -> bool {
+    match key {
+        Key::Enter => true,
+        Key::Character(character) => character.eq_ignore_ascii_case("y"),
+        _ => false,
+    }
+}
+
+fn is_no(key: &Key) -> bool {
+    match key {
+        Key::Escape => true,
+        Key::Character(character) => character.eq_ignore_ascii_case("n"),
+        _ => false,
+    }
+}
+
+// Writes unsaved work to the recovery file every interval
+pub fn editor_autosave_system(
+    time: Res<Time>,
+    mut autosave: ResMut<EditorAutosave>,
+    editor_level: Res<EditorLevel>,
+    object_query: Query<(&EditorObjectId, &EditorObject)>,
+) {
+    if autosave.timer.tick(time.delta()).just_finished() {
+        let level = editor_level.with_objects(object_query.iter());
+        autosave.write(&editor_level, &level);
+    }
+}
+
+// Leaving the editor, for a playtest or otherwise, autosaves right away
+pub fn editor_exit_autosave_system(
+    mut autosave: ResMut<EditorAutosave>,
+    editor_level: Option<Res<EditorLevel>>,
+    prompt: Option<Res<EditorPrompt>>,
+    object_query: Query<(&EditorObjectId, &EditorObject)>,
+) {
+    // An unanswered restore prompt still needs the recovery file as it is
+    let (Some(editor_level), None) = (editor_level, prompt) else {
+        return;
+    };
+    let level = editor_level.with_objects(object_query.iter());
+    autosave.write(&editor_level, &level);
+}
+
+// Keeps the panel labels, the prompt and the save message up to date
+pub fn editor_panel_text_system(
+    time: Res<Time>,
+    editor_level: Res<EditorLevel>,
+    prompt: Option<Res<EditorPrompt>>,
+    mut message: ResMut<EditorMessage>,
+    button_query: Query<(&EditorPanelButton, &Children)>,
+    mut text_query: Query<&mut Text, Without<EditorNoticeText>>,
+    mut notice_query: Query<&mut Text, With<EditorNoticeText>>,
+) {
+    message.timer.tick(time.delta());
+    let level = &editor_level.level;
+    let editing = |field: EditorPanelButton| match prompt.as_deref() {
+        Some(EditorPrompt::Edit { field: edited, value, .. }) if *edited == field => Some(format!("{}_", value)),
+        _ => None,
+    };
+
+    for (button, children) in button_query.iter() {
+        let label = match button {
+            EditorPanelButton::LevelId => {
+                let value = editing(*button).unwrap_or_else(|| level.level_id.to_string());
+                format!("Level ID: {}", value)
+            }
+            EditorPanelButton::Music => {
+                let value = editing(*button).unwrap_or_else(|| level.music.clone().unwrap_or_else(|| "none".to_string()));
+                format!("Music: {}", value)
+            }
+            EditorPanelButton::Platformer => {
+                format!("Platformer: {}", if level.platformer.unwrap_or(false) { "on" } else { "off" })
+            }
+            EditorPanelButton::Save => "Save (Ctrl+S)".to_string(),
+            EditorPanelButton::SaveAs => "Save As (Ctrl+Shift+S)".to_string(),
+            EditorPanelButton::Playtest => "Playtest (P, Shift+P from cursor)".to_string(),
+            EditorPanelButton::Back => "Back (Esc)".to_string(),
+        };
+        let mut texts = text_query.iter_many_mut(children);
+        while let Some(mut text) = texts.fetch_next() {
+            if text.sections[0].value != label {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+
+    let notice = match prompt.as_deref() {
+        Some(EditorPrompt::Edit { field: EditorPanelButton::LevelId, .. }) => {
+            "Type an ID or a file like level_3.json. Enter keeps it, Escape cancels".to_string()
+        }
+        Some(EditorPrompt::Edit { .. }) => "Enter keeps the value, Escape cancels".to_string(),
+        Some(EditorPrompt::ConfirmOverwrite { level_id }) => {
+            format!("Level {} already exists. Overwrite it? (Y/N)", level_id)
+        }
+        Some(EditorPrompt::Restore { recovery }) => {
+            format!("Unsaved work on level {} from {} was found. Restore it? (Y/N)", recovery.level.level_id, recovery.written_at)
+        }
+        Some(EditorPrompt::ConfirmLeave) => {
+            format!("Level {} has unsaved changes. Leave without saving? (Y/N)", level.level_id)
+        }
+        None if !message.timer.finished() => message.text.clone(),
+        None => String::new(),
+    };
+    for mut text in notice_query.iter_mut() {
+        if text.sections[0].value != notice {
+            text.sections[0].value = notice.clone();
+        }
+    }
+}
+
+// Area an object covers in the level
+pub fn object_world_bounds(object: &LevelObject) -> Rect {
+    let bounds = object_bounds(object);
+    let position = object.position();
+    Rect::from_corners(bounds.min + position, bounds.max + position)
+}
+
+// Smallest object under a point, so small objects in front of large ones stay reachable
+fn object_at<'a>(point: Vec2, objects: impl IntoIterator<Item = (Entity, &'a LevelObject)>) -> Option<Entity> {
+    objects
+        .into_iter()
+        .map(|(entity, object)| (entity, object_world_bounds(object)))
+        .filter(|(_, bounds)| bounds.contains(point))
+        .min_by(|(_, a), (_, b)| a.size().element_product().total_cmp(&b.size().element_product()))
+        .map(|(entity, _)| entity)
+}
+
+// Drag in progress with the select tool
+pub enum SelectDrag {
+    // Moves the selection, from each object as it was when the drag began
+    Move { start: Vec2, origins: Vec<(Entity, LevelObject)> },
+    // Selects every object the box touches
+    Box { start: Vec2 },
+}
+
+// With the select tool, clicking an object selects it and dragging moves the selection.
+// Dragging over empty space selects with a box. Shift adds to the selection.
+#[allow(clippy::too_many_arguments)]
+pub fn editor_select_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    ui_query: Query<&Interaction, With<EditorUi>>,
+    tool: Res<EditorTool>,
+    grid: Res<EditorGrid>,
+    mut selection: ResMut<EditorSelection>,
+    mut history: ResMut<EditorHistory>,
+    mut object_query: Query<(Entity, &EditorObjectId, &mut EditorObject)>,
+    mut drag: Local<Option<SelectDrag>>,
+    mut last_cursor_position: Local<Vec2>,
+    mut gizmos: Gizmos,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_query.get_single()) else {
+        return;
+    };
+    let cursor_position = match cursor_world_position(window, camera, camera_transform) {
+        Some(position) => {
+            *last_cursor_position = position;
+            position
+        }
+        // A drag that leaves the window stays where the cursor left, releasing there ends it
+        None if drag.is_some() => *last_cursor_position,
+        None => return,
+    };
+    let adding = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    let over_ui = ui_query.iter().any(|interaction| *interaction != Interaction::None);
+    if *tool == EditorTool::Select && mouse_button_input.just_pressed(MouseButton::Left) && !over_ui {
+        let objects = object_query.iter().map(|(entity, _, editor_object)| (entity, &editor_object.object));
+        match object_at(cursor_position, objects) {
+            Some(entity) if adding => {
+                if !selection.entities.remove(&entity) {
+                    selection.entities.insert(entity);
+                }
+            }
+            Some(entity) => {
+                if !selection.entities.contains(&entity) {
+                    selection.entities.clear();
+                    selection.entities.insert(entity);
+                }
+                let origins = selection.entities
+                    .iter()
+                    .filter_map(|&entity| object_query.get(entity).ok())
+                    .map(|(entity, _, editor_object)| (entity, editor_object.object.clone()))
+                    .collect();
+                *drag = Some(SelectDrag::Move { start: cursor_position, origins });
+            }
+            None => {
+                if !adding {
+                    selection.entities.clear();
+                }
+                *drag = Some(SelectDrag::Box { start: cursor_position });
+            }
+        }
+    }
+
+    match drag.as_ref() {
+        Some(SelectDrag::Move { start, origins }) => {
+            // Moves snap by whole grid steps, keeping objects where they were relative to the grid
+            let offset = cursor_position - *start;
+            let offset = if offset.length() < EDITOR_DRAG_THRESHOLD {
+                Vec2::ZERO
+            } else if snapping_disabled(&keyboard_input) {
+                offset
+            } else {
+                grid.snap(offset)
+            };
+            let mut edits = Vec::new();
+            for (entity, origin) in origins.iter() {
+                if let Ok((_, &id, mut editor_object)) = object_query.get_mut(*entity) {
+                    let position = origin.position() + offset;
+                    if editor_object.object.position() != position {
+                        editor_object.object.set_position(position);
+                    }
+                    if offset != Vec2::ZERO {
+                        edits.push((id, origin.clone(), editor_object.object.clone()));
+                    }
+                }
+            }
+            // The whole drag is one step of the history
+            if !mouse_button_input.pressed(MouseButton::Left) {
+                history.record(EditorCommand::Edit(edits));
+            }
+        }
+        Some(SelectDrag::Box { start }) => {
+            let area = Rect::from_corners(*start, cursor_position);
+            if !mouse_button_input.pressed(MouseButton::Left) {
+                for (entity, _, editor_object) in object_query.iter() {
+                    if !object_world_bounds(&editor_object.object).intersect(area).is_empty() {
+                        selection.entities.insert(entity);
+                    }
+                }
+            } else {
+                gizmos.rect_2d(area.center(), 0.0, area.size(), Color::srgb(0.3, 0.7, 1.0));
+            }
+        }
+        None => {}
+    }
+
+    if !mouse_button_input.pressed(MouseButton::Left) {
+        *drag = None;
+    }
+}
+
+// Rotates, scales and flips the selection around its center, or deletes it.
+// Q and E turn by 90°, = and - double or halve, F flips horizontally and Shift+F vertically.
+// Holding Alt turns and scales in small steps.
+pub fn editor_transform_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut selection: ResMut<EditorSelection>,
+    mut history: ResMut<EditorHistory>,
+    mut object_query: Query<(&EditorObjectId, &mut EditorObject)>,
+) {
+    // Stale entries would otherwise keep the selection around deleted objects
+    selection.entities.retain(|&entity| object_query.contains(entity));
+    if selection.entities.is_empty() {
+        return;
+    }
+    // Control combinations belong to other editor shortcuts
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        let mut removed = Vec::new();
+        for entity in selection.entities.drain() {
+            if let Ok((&id, editor_object)) = object_query.get(entity) {
+                removed.push((id, editor_object.object.clone()));
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+        history.record(EditorCommand::Remove(removed));
+        return;
+    }
+
+    let free = snapping_disabled(&keyboard_input);
+    let rotation = if free { EDITOR_FREE_ROTATION_STEP.to_radians() } else { std::f32::consts::FRAC_PI_2 };
+    let scale = if free { EDITOR_FREE_SCALE_STEP } else { EDITOR_SCALE_STEP };
+    let vertical = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let matrix = if keyboard_input.just_pressed(KeyCode::KeyQ) {
+        Mat2::from_angle(rotation)
+    } else if keyboard_input.just_pressed(KeyCode::KeyE) {
+        Mat2::from_angle(-rotation)
+    } else if keyboard_input.just_pressed(KeyCode::Equal) {
+        Mat2::from_diagonal(Vec2::splat(scale))
+    } else if keyboard_input.just_pressed(KeyCode::Minus) {
+        Mat2::from_diagonal(Vec2::splat(1.0 / scale))
+    } else if keyboard_input.just_pressed(KeyCode::KeyF) && vertical {
+        Mat2::from_diagonal(Vec2::new(1.0, -1.0))
+    } else if keyboard_input.just_pressed(KeyCode::KeyF) {
+        Mat2::from_diagonal(Vec2::new(-1.0, 1.0))
+    } else {
+        return;
+    };
+
+    let mut selected = object_query.iter_many_mut(selection.entities.iter());
+    let mut bounds: Option<Rect> = None;
+    while let Some((_, editor_object)) = selected.fetch_next() {
+        let object_bounds = object_world_bounds(&editor_object.object);
+        bounds = Some(bounds.map_or(object_bounds, |bounds| bounds.union(object_bounds)));
+    }
+    let Some(bounds) = bounds else {
+        return;
+    };
+
+    let mut edits = Vec::new();
+    let mut selected = object_query.iter_many_mut(selection.entities.iter());
+    while let Some((&id, mut editor_object)) = selected.fetch_next() {
+        let before = editor_object.object.clone();
+        transform_object(&mut editor_object.object, bounds.center(), matrix);
+        edits.push((id, before, editor_object.object.clone()));
+    }
+    history.record(EditorCommand::Edit(edits));
+}
+
+// L toggles whether the selected obstacles kill, M cycles the game mode of selected portals
+pub fn editor_property_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    selection: Res<EditorSelection>,
+    mut history: ResMut<EditorHistory>,
+    mut object_query: Query<(&EditorObjectId, &mut EditorObject)>,
+) {
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let toggle_lethal = keyboard_input.just_pressed(KeyCode::KeyL);
+    let cycle_mode = keyboard_input.just_pressed(KeyCode::KeyM);
+    if !toggle_lethal && !cycle_mode {
+        return;
+    }
+
+    let mut edits = Vec::new();
+    let mut selected = object_query.iter_many_mut(selection.entities.iter());
+    while let Some((&id, mut editor_object)) = selected.fetch_next() {
+        let LevelObject::Obstacle(data) = &editor_object.object else {
+            continue;
+        };
+        let mut data = data.clone();
+        let mut changed = false;
+        if toggle_lethal {
+            data.non_lethal = Some(!data.non_lethal.unwrap_or(false));
+            changed = true;
+        }
+        // Only portals have a mode, the other objects still take the L toggle
+        if cycle_mode {
+            if let Some(mode) = data.portal {
+                data.portal = Some(mode.next());
+                changed = true;
+            }
+        }
+        if !changed {
+            continue;
+        }
+        let before = std::mem::replace(&mut editor_object.object, LevelObject::Obstacle(data));
+        edits.push((id, before, editor_object.object.clone()));
+    }
+    history.record(EditorCommand::Edit(edits));
+}
+
+// Copied objects, as level object JSON that other levels, sessions and text editors can paste.
+// Kept for the whole run, on some platforms the copied text goes away with the clipboard.
+pub struct EditorClipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Default for EditorClipboard {
+    fn default() -> Self {
+        let system = arboard::Clipboard::new()
+            .map_err(|error| warn!("No system clipboard, copying to {} instead: {}", EDITOR_CLIPBOARD_FALLBACK_PATH, error))
+            .ok();
+        Self { system }
+    }
+}
+
+impl EditorClipboard {
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        match self.system.as_mut() {
+            Some(clipboard) => clipboard.set_text(text).map_err(|error| error.to_string()),
+            None => fs::write(EDITOR_CLIPBOARD_FALLBACK_PATH, text).map_err(|error| error.to_string()),
+        }
+    }
+
+    fn text(&mut self) -> Option<String> {
+        match self.system.as_mut() {
+            Some(clipboard) => clipboard.get_text().ok(),
+            None => fs::read_to_string(EDITOR_CLIPBOARD_FALLBACK_PATH).ok(),
+        }
+    }
+}
+
+// Ctrl+C copies the selection, Ctrl+X cuts it, Ctrl+V pastes at the cursor and Ctrl+D
+// duplicates the selection one grid cell to the right. Pasted objects are selected.
+#[allow(clippy::too_many_arguments)]
+pub fn editor_clipboard_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut editor_level: ResMut<EditorLevel>,
+    grid: Res<EditorGrid>,
+    mut history: ResMut<EditorHistory>,
+    mut selection: ResMut<EditorSelection>,
+    mut clipboard: NonSendMut<EditorClipboard>,
+    object_query: Query<(&EditorObjectId, &EditorObject)>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let selected = || {
+        object_query
+            .iter_many(selection.entities.iter())
+            .map(|(&id, editor_object)| (id, editor_object.object.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    if keyboard_input.just_pressed(KeyCode::KeyC) || keyboard_input.just_pressed(KeyCode::KeyX) {
+        let objects = selected();
+        if objects.is_empty() {
+            return;
+        }
+        let copied = objects.iter().map(|(_, object)| object.clone()).collect::<Vec<_>>();
+        let serialized = serde_json::to_string_pretty(&copied).expect("Failed to serialize clipboard");
+        if let Err(error) = clipboard.set_text(serialized) {
+            warn!("Could not copy to the clipboard: {}", error);
+            return;
+        }
+        info!("Copied {} objects.", copied.len());
+
+        if keyboard_input.just_pressed(KeyCode::KeyX) {
+            for entity in selection.entities.drain() {
+                commands.entity(entity).despawn_recursive();
+            }
+            history.record(EditorCommand::Remove(objects));
+        }
+        return;
+    }
+
+    let (objects, offset) = if keyboard_input.just_pressed(KeyCode::KeyV) {
+        let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_query.get_single()) else {
+            return;
+        };
+        let Some(cursor_position) = cursor_world_position(window, camera, camera_transform) else {
+            return;
+        };
+        let Some(text) = clipboard.text() else {
+            return;
+        };
+        // Anything else on the clipboard, like text copied elsewhere, is left alone
+        let objects = match serde_json::from_str::<Vec<LevelObject>>(&text) {
+            Ok(objects) => objects,
+            Err(error) => {
+                debug!("The clipboard is not a list of level objects: {}", error);
+                return;
+            }
+        };
+        let Some(bounds) = objects.iter().map(object_world_bounds).reduce(|a, b| a.union(b)) else {
+            return;
+        };
+        // Centers the group on the cursor, moving by whole grid steps so it stays aligned
+        let offset = cursor_position - bounds.center();
+        let offset = if snapping_disabled(&keyboard_input) { offset } else { grid.snap(offset) };
+        (objects, offset)
+    } else if keyboard_input.just_pressed(KeyCode::KeyD) {
+        let objects = selected().into_iter().map(|(_, object)| object).collect::<Vec<_>>();
+        (objects, Vec2::new(grid.cell_size, 0.0))
+    } else {
+        return;
+    };
+
+    selection.entities.clear();
+    let mut added = Vec::new();
+    for mut object in objects {
+        object.set_position(object.position() + offset);
+        let id = editor_level.new_object_id();
+        added.push((id, object.clone()));
+        let entity = spawn_editor_object(&mut commands, &mut meshes, &mut materials, &editor_level.level, id, object);
+        selection.entities.insert(entity);
+    }
+    history.record(EditorCommand::Add(added));
+}
+
+// Ctrl+Z undoes the last step, Ctrl+Y or Ctrl+Shift+Z redoes it. Restored objects are selected.
+#[allow(clippy::too_many_arguments)]
+pub fn editor_history_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    editor_level: Res<EditorLevel>,
+    mut history: ResMut<EditorHistory>,
+    mut selection: ResMut<EditorSelection>,
+    mut object_query: Query<(Entity, &EditorObjectId, &mut EditorObject)>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let command = if keyboard_input.just_pressed(KeyCode::KeyZ) && !shift {
+        history.undo()
+    } else if keyboard_input.just_pressed(KeyCode::KeyY) || keyboard_input.just_pressed(KeyCode::KeyZ) && shift {
+        history.redo()
+    } else {
+        return;
+    };
+    let Some(command) = command else {
+        return;
+    };
+
+    selection.entities.clear();
+    match command {
+        EditorCommand::Add(objects) => {
+            for (id, object) in objects {
+                let entity = spawn_editor_object(&mut commands, &mut meshes, &mut materials, &editor_level.level, id, object);
+                selection.entities.insert(entity);
+            }
+        }
+        EditorCommand::Remove(objects) => {
+            for (entity, id, _) in object_query.iter() {
+                if objects.iter().any(|(removed, _)| removed == id) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+        EditorCommand::Edit(edits) => {
+            for (entity, id, mut editor_object) in object_query.iter_mut() {
+                if let Some((_, _, after)) = edits.iter().find(|(edited, _, _)| edited == id) {
+                    editor_object.object = after.clone();
+                    selection.entities.insert(entity);
+                }
+            }
+        }
+    }
+}
+
+// Rounds away the float error of rotations, so turned objects land back on round numbers
+fn tidy(value: Vec2) -> Vec2 {
+    (value * 1000.0).round() / 1000.0
+}
+
+// Applies a rotation, scale or flip around a pivot to an object's geometry. Obstacles keep
+// their position at the bottom left corner of their shape, as in the level files.
+pub fn transform_object(object: &mut LevelObject, pivot: Vec2, matrix: Mat2) {
+    let apply = |point: Vec2| tidy(pivot + matrix * (point - pivot));
+    match object {
+        LevelObject::Obstacle(data) => {
+            let points = data.shape().map(|[x, y]| apply(data.position + Vec2::new(x, y)));
+            let min = points.iter().copied().fold(Vec2::splat(f32::MAX), Vec2::min);
+
+            match data.slope.as_mut() {
+                // Slopes are rebuilt from their angle, so only flips and scales carry over
+                Some(slope) => {
+                    let (x_axis, y_axis) = (matrix.x_axis, matrix.y_axis);
+                    if x_axis.y != 0.0 || y_axis.x != 0.0 || y_axis.y <= 0.0 || x_axis.x.abs() != y_axis.y {
+                        debug!("Slopes can only be flipped and scaled.");
+                        return;
+                    }
+                    if x_axis.x < 0.0 {
+                        slope.descending = Some(!slope.descending.unwrap_or(false));
+                    }
+                    let factor = x_axis.x.abs();
+                    for vertex in data.vertices.iter_mut() {
+                        vertex[0] *= factor;
+                    }
+                }
+                None => {
+                    data.vertices = points.map(|point| (point - min).to_array());
+                }
+            }
+            data.position = min;
+
+            // A dash orb's direction turns with it
+            if let Some(dash_orb) = data.dash_orb.as_mut() {
+                let direction = matrix * Vec2::from_angle(dash_orb.angle.to_radians());
+                dash_orb.angle = (direction.to_angle().to_degrees() * 1000.0).round() / 1000.0;
+            }
+        }
+        LevelObject::Coin(data) => data.position = apply(data.position),
+        LevelObject::StartPosition(data) => data.position = apply(data.position),
+        LevelObject::Sawblade(data) => {
+            data.position = apply(data.position);
+            data.radius *= matrix.determinant().abs().sqrt();
+        }
+        LevelObject::Trigger(data) => {
+            data.position = apply(data.position);
+            // The box that holds the turned area, triggers stay axis aligned
+            let size = data.size.unwrap_or(Vec2::splat(30.0));
+            let size = matrix.x_axis.abs() * size.x + matrix.y_axis.abs() * size.y;
+            data.size = Some(tidy(size));
+        }
+    }
+}
+
+// Follows edits to editor objects with their transforms and visuals
+pub fn editor_object_sync_system(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    editor_level: Res<EditorLevel>,
+    mut object_query: Query<(&EditorObject, &mut Transform, &mut Mesh2dHandle, &Handle<ColorMaterial>), Changed<EditorObject>>,
+) {
+    for (editor_object, mut transform, mut mesh, material) in object_query.iter_mut() {
+        transform.translation = editor_object.object.position().extend(transform.translation.z);
+        let (new_mesh, color) = object_visual(&editor_level.level, &editor_object.object);
+        meshes.remove(&mesh.0);
+        mesh.0 = meshes.add(new_mesh);
+        if let Some(material) = materials.get_mut(material) {
+            material.color = color;
+        }
+    }
+}
+
+// Outlines every selected object
+pub fn editor_selection_highlight_system(
+    selection: Res<EditorSelection>,
+    object_query: Query<&EditorObject>,
+    mut gizmos: Gizmos,
+) {
+    for editor_object in object_query.iter_many(selection.entities.iter()) {
+        let bounds = object_world_bounds(&editor_object.object);
+        gizmos.rect_2d(bounds.center(), 0.0, bounds.size() + Vec2::splat(4.0), Color::srgb(1.0, 0.9, 0.2));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn cleanup_editor(
+    mut commands: Commands,
+    editor_entities: Query<Entity, Or<(With<EditorCamera>, With<EditorUi>, With<EditorGridLines>, With<PalettePreview>)>>,
+    obstacle_entities: Query<Entity, Or<(With<EditorObject>, With<Floor>)>>,
+) {
+    commands.insert_resource(ClearColor::default());
+    commands.remove_resource::<EditorLevel>();
+    commands.insert_resource(EditorSelection::default());
+    commands.remove_resource::<EditorPrompt>();
+
+    // Cleanup editor camera and UI
+    for entity in editor_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // Cleanup obstacles
+    for entity in obstacle_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+    use crate::levels::{SlopeAngle, SlopeData};
+
+    fn obstacle(position: Vec2, vertices: [[f32; 2]; 4]) -> LevelObject {
+        LevelObject::Obstacle(ObstacleData { position, vertices, ..default() })
+    }
+
+    fn rectangle(position: Vec2, size: Vec2) -> LevelObject {
+        obstacle(position, [[0.0, 0.0], [size.x, 0.0], [size.x, size.y], [0.0, size.y]])
+    }
+
+    fn obstacle_data(object: &LevelObject) -> &ObstacleData {
+        match object {
+            LevelObject::Obstacle(data) => data,
+            _ => panic!("not an obstacle"),
+        }
+    }
+
+    #[test]
+    fn rotating_turns_the_shape_around_the_pivot() {
+        let mut object = rectangle(Vec2::ZERO, Vec2::new(60.0, 30.0));
+        transform_object(&mut object, Vec2::new(30.0, 15.0), Mat2::from_angle(FRAC_PI_2));
+
+        let bounds = object_world_bounds(&object);
+        assert_eq!(bounds.min, Vec2::new(15.0, -15.0));
+        assert_eq!(bounds.max, Vec2::new(45.0, 45.0));
+        assert_eq!(object.position(), bounds.min);
+    }
+
+    #[test]
+    fn flipping_mirrors_the_vertices_in_place() {
+        let mut object = obstacle(Vec2::new(100.0, 0.0), [[0.0, 0.0], [30.0, 0.0], [10.0, 30.0], [0.0, 0.0]]);
+        transform_object(&mut object, Vec2::new(115.0, 15.0), Mat2::from_diagonal(Vec2::new(-1.0, 1.0)));
+
+        let data = obstacle_data(&object);
+        assert_eq!(data.position, Vec2::new(100.0, 0.0));
+        assert_eq!(data.vertices, [[30.0, 0.0], [0.0, 0.0], [20.0, 30.0], [30.0, 0.0]]);
+    }
+
+    #[test]
+    fn dash_orbs_turn_their_direction() {
+        let mut object = rectangle(Vec2::ZERO, Vec2::splat(30.0));
+        if let LevelObject::Obstacle(data) = &mut object {
+            data.dash_orb = Some(DashOrbData { angle: 0.0, speed: None });
+        }
+        transform_object(&mut object, Vec2::splat(15.0), Mat2::from_angle(FRAC_PI_2));
+
+        assert_eq!(obstacle_data(&object).dash_orb.map(|orb| orb.angle), Some(90.0));
+    }
+
+    #[test]
+    fn slopes_flip_and_scale_but_do_not_rotate() {
+        let mut object = rectangle(Vec2::ZERO, Vec2::new(60.0, 60.0));
+        if let LevelObject::Obstacle(data) = &mut object {
+            data.slope = Some(SlopeData { angle: SlopeAngle::Steep, descending: None });
+        }
+
+        transform_object(&mut object, Vec2::splat(30.0), Mat2::from_angle(FRAC_PI_2));
+        assert_eq!(obstacle_data(&object).slope.and_then(|slope| slope.descending), None);
+
+        transform_object(&mut object, Vec2::splat(30.0), Mat2::from_diagonal(Vec2::new(-1.0, 1.0)));
+        assert_eq!(obstacle_data(&object).slope.and_then(|slope| slope.descending), Some(true));
+
+        transform_object(&mut object, Vec2::ZERO, Mat2::from_diagonal(Vec2::splat(2.0)));
+        assert_eq!(object_world_bounds(&object).max.x, 120.0);
+    }
+
+    fn add(id: u64) -> EditorCommand {
+        EditorCommand::Add(vec![(EditorObjectId(id), rectangle(Vec2::ZERO, Vec2::splat(30.0)))])
+    }
+
+    // Ids a command adds, or removes as negative numbers
+    fn ids(command: &EditorCommand) -> Vec<i64> {
+        match command {
+            EditorCommand::Add(objects) => objects.iter().map(|(id, _)| id.0 as i64).collect(),
+            EditorCommand::Remove(objects) => objects.iter().map(|(id, _)| -(id.0 as i64)).collect(),
+            EditorCommand::Edit(edits) => edits.iter().map(|(id, _, _)| id.0 as i64).collect(),
+        }
+    }
+
+    #[test]
+    fn undo_reverts_and_redo_repeats_the_last_step() {
+        let mut history = EditorHistory::default();
+        history.record(add(1));
+        history.record(add(2));
+
+        assert_eq!(history.undo().map(|command| ids(&command)), Some(vec![-2]));
+        assert_eq!(history.undo().map(|command| ids(&command)), Some(vec![-1]));
+        assert!(history.undo().is_none());
+        assert_eq!(history.redo().map(|command| ids(&command)), Some(vec![1]));
+        assert_eq!(history.undo().map(|command| ids(&command)), Some(vec![-1]));
+    }
+
+    #[test]
+    fn recording_a_new_step_clears_redo() {
+        let mut history = EditorHistory::default();
+        history.record(add(1));
+        history.undo();
+        history.record(add(2));
+
+        assert!(history.redo().is_none());
+        assert_eq!(history.undo().map(|command| ids(&command)), Some(vec![-2]));
+    }
+
+    #[test]
+    fn empty_steps_are_not_recorded() {
+        let mut history = EditorHistory::default();
+        history.record(add(1));
+        history.undo();
+        history.record(EditorCommand::Edit(Vec::new()));
+
+        assert!(history.redo().is_some());
+    }
+
+    #[test]
+    fn oldest_steps_are_forgotten_past_the_depth() {
+        let mut history = EditorHistory::with_depth(3);
+        for id in 1..=5 {
+            history.record(add(id));
+        }
+
+        let undone = std::iter::from_fn(|| history.undo()).flat_map(|command| ids(&command)).collect::<Vec<_>>();
+        assert_eq!(undone, vec![-5, -4, -3]);
+    }
+
+    #[test]
+    fn object_at_picks_the_smallest_object_under_the_point() {
+        let large = rectangle(Vec2::ZERO, Vec2::splat(300.0));
+        let small = rectangle(Vec2::new(90.0, 90.0), Vec2::splat(30.0));
+        let (large_entity, small_entity) = (Entity::from_raw(1), Entity::from_raw(2));
+        let objects = [(large_entity, &large), (small_entity, &small)];
+
+        assert_eq!(object_at(Vec2::new(100.0, 100.0), objects), Some(small_entity));
+        assert_eq!(object_at(Vec2::new(10.0, 10.0), objects), Some(large_entity));
+        assert_eq!(object_at(Vec2::new(400.0, 10.0), objects), None);
+    }
+
+    #[test]
+    fn only_changed_levels_ask_before_leaving() {
+        let level = Level { level_id: 3, ..default() };
+        let mut autosave = EditorAutosave::default();
+        autosave.set_baseline(&level);
+        assert!(!autosave.has_changes(&level));
+
+        let mut changed = level.clone();
+        changed.music = Some("audio/song.ogg".to_string());
+        assert!(autosave.has_changes(&changed));
+    }
+
+    #[test]
+    fn recovered_work_is_only_offered_for_its_own_level() {
+        let recovery = EditorRecovery {
+            version: EDITOR_RECOVERY_VERSION,
+            written_at: String::new(),
+            saved_id: Some(2),
+            level: Level { level_id: 2, ..default() },
+        };
+        assert!(recovery.offered_for(Some(2)));
+        assert!(!recovery.offered_for(Some(3)));
+        assert!(!recovery.offered_for(None));
+
+        let unsaved = EditorRecovery { saved_id: None, ..recovery };
+        assert!(unsaved.offered_for(None));
+        assert!(!unsaved.offered_for(Some(2)));
     }
 }
@@ -1,29 +1,556 @@
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::Path;
+use crate::components::GameMode;
 
-#[derive(Serialize, Deserialize)]
+// Where the player spawns when a run starts from the beginning of a level
+pub const PLAYER_START: Vec2 = Vec2::new(-200.0, 6.0);
+
+// Reserved color channel IDs, user channels use any other ID
+pub const BACKGROUND_CHANNEL: u32 = 1000;
+pub const GROUND_CHANNEL: u32 = 1001;
+pub const LINE_CHANNEL: u32 = 1002;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct Level {
     pub level_id: usize,
     pub obstacles: Vec<ObstacleData>,
+    #[serde(default)]
+    pub start_positions: Vec<StartPositionData>,
+    #[serde(default)]
+    pub coins: Vec<CoinData>,
+    #[serde(default)]
+    pub triggers: Vec<TriggerData>,
+    #[serde(default)]
+    pub color_channels: Vec<ColorChannelData>,
+    #[serde(default)]
+    pub sawblades: Vec<SawbladeData>,
+    pub platformer: Option<bool>, // Player controls horizontal movement instead of auto-scrolling
+    pub music: Option<String>, // Asset path of the song played during the level
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ObstacleData {
     pub position: Vec2,
     pub vertices: [[f32; 2]; 4], // Define vertices of the triangle
     pub non_lethal: Option<bool>,
     pub is_finish: Option<bool>,
     pub color: Option<[f32; 3]>,
+    pub groups: Option<Vec<u32>>, // Group IDs that triggers can target
+    pub color_channel: Option<u32>, // Takes precedence over `color`
+    pub slope: Option<SlopeData>,
+    pub portal: Option<GameMode>, // Switches the player's game mode on contact
+    pub dash_orb: Option<DashOrbData>,
+    pub teleport: Option<TeleportData>,
+    pub fade: Option<FadeData>, // Opacity follows the distance to the player
+    pub collision_block: Option<u32>, // Invisible block with this ID that collision triggers watch
+    pub pad: Option<f32>, // Jump pad, launches the player with this vertical speed on contact
+    pub decoration: Option<bool>, // Only drawn, never collides
+}
+
+impl ObstacleData {
+    // The vertices to draw and collide with. Slopes are rebuilt from their angle so the
+    // surface always matches the physics, using the width of `vertices`.
+    pub fn shape(&self) -> [[f32; 2]; 4] {
+        match self.slope {
+            Some(slope) => {
+                let width = self.vertices.iter().map(|&[x, _]| x).fold(1.0, f32::max);
+                let height = width * slope.angle.gradient();
+                if slope.descending.unwrap_or(false) {
+                    [[0.0, 0.0], [width, 0.0], [0.0, height], [0.0, 0.0]]
+                } else {
+                    [[0.0, 0.0], [width, 0.0], [width, height], [0.0, 0.0]]
+                }
+            }
+            None => self.vertices,
+        }
+    }
+}
+
+// Orb that moves the player in a straight line for as long as jump is held
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DashOrbData {
+    pub angle: f32, // Degrees above the horizontal, mirrored when gravity is flipped
+    pub speed: Option<f32>, // Defaults to the player's speed
+}
+
+// Entrance of a teleport portal pair, the exit is drawn at `exit` with the same shape
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct TeleportData {
+    pub exit: Vec2,
+}
+
+// Fades an object in or out as the player gets close, for invisible and memory sections.
+// Only the look changes, the collider stays the same at any opacity.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct FadeData {
+    pub distance: f32, // Horizontal distance to the player where `near_opacity` is reached
+    pub length: Option<f32>, // Distance over which the fade happens, defaults to 90
+    pub near_opacity: Option<f32>, // Defaults to 1, fully visible up close
+    pub far_opacity: Option<f32>, // Defaults to 0, hidden further away
+    pub easing: Option<Easing>,
+}
+
+impl FadeData {
+    pub fn opacity(&self, distance: f32) -> f32 {
+        let length = self.length.unwrap_or(90.0).max(0.001);
+        let t = (distance - self.distance) / length;
+        let near = self.near_opacity.unwrap_or(1.0);
+        let far = self.far_opacity.unwrap_or(0.0);
+        near + (far - near) * self.easing.unwrap_or_default().apply(t)
+    }
+}
+
+// Triangle the player can run up or down instead of crashing into
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SlopeData {
+    pub angle: SlopeAngle,
+    pub descending: Option<bool>, // Rises to the left instead of the right
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SlopeAngle {
+    Gentle, // 22.5 degrees
+    Steep,  // 45 degrees
+}
+
+impl SlopeAngle {
+    // Rise per unit of width
+    pub fn gradient(self) -> f32 {
+        match self {
+            SlopeAngle::Gentle => 22.5_f32.to_radians().tan(),
+            SlopeAngle::Steep => 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ColorChannelData {
+    pub id: u32,
+    pub color: [f32; 3],
+    pub alpha: Option<f32>,
+    pub blending: Option<bool>, // Additive blending, only applies to level objects
+}
+
+impl Level {
+    // The level's color channels, with defaults for any reserved channel it leaves out
+    pub fn color_channels_with_defaults(&self) -> Vec<ColorChannelData> {
+        let mut channels = self.color_channels.clone();
+        let defaults = [
+            (BACKGROUND_CHANNEL, [0.17, 0.17, 0.18]),
+            (GROUND_CHANNEL, [1.0, 1.0, 1.0]),
+            (LINE_CHANNEL, [1.0, 1.0, 1.0]),
+        ];
+        for (id, color) in defaults {
+            if !channels.iter().any(|channel| channel.id == id) {
+                channels.push(ColorChannelData { id, color, alpha: None, blending: None });
+            }
+        }
+        channels
+    }
+
+    // Moves every object out of the level, in the order of the level's lists
+    pub fn take_objects(&mut self) -> Vec<LevelObject> {
+        let mut objects = Vec::new();
+        objects.extend(std::mem::take(&mut self.obstacles).into_iter().map(LevelObject::Obstacle));
+        objects.extend(std::mem::take(&mut self.coins).into_iter().map(LevelObject::Coin));
+        objects.extend(std::mem::take(&mut self.sawblades).into_iter().map(LevelObject::Sawblade));
+        objects.extend(std::mem::take(&mut self.triggers).into_iter().map(LevelObject::Trigger));
+        objects.extend(std::mem::take(&mut self.start_positions).into_iter().map(LevelObject::StartPosition));
+        objects
+    }
+
+    // Problems that keep the level from playing as intended, as messages for the designer
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !self.obstacles.iter().any(|obstacle| obstacle.is_finish.unwrap_or(false)) {
+            problems.push("The level has no finish line.".to_string());
+        }
+
+        let channels = self.color_channels_with_defaults();
+        let channel_exists = |id: u32| channels.iter().any(|channel| channel.id == id);
+        let block_ids = self.obstacles.iter().filter_map(|obstacle| obstacle.collision_block).collect::<Vec<_>>();
+        let object_groups = self.obstacles
+            .iter()
+            .filter_map(|obstacle| obstacle.groups.as_ref())
+            .chain(self.sawblades.iter().filter_map(|sawblade| sawblade.groups.as_ref()))
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        for obstacle in self.obstacles.iter() {
+            let at = obstacle.position;
+            // Shoelace formula over the drawn quad
+            let shape = obstacle.shape();
+            let area = (0..4)
+                .map(|i| shape[i][0] * shape[(i + 1) % 4][1] - shape[(i + 1) % 4][0] * shape[i][1])
+                .sum::<f32>()
+                / 2.0;
+            if area.abs() < 1.0 {
+                problems.push(format!("Obstacle at ({}, {}) has no area.", at.x, at.y));
+            }
+            if let Some(channel) = obstacle.color_channel.filter(|&channel| !channel_exists(channel)) {
+                problems.push(format!("Obstacle at ({}, {}) uses color channel {}, which is not defined.", at.x, at.y, channel));
+            }
+        }
+
+        for trigger in self.triggers.iter() {
+            let at = trigger.position;
+            match &trigger.action {
+                TriggerAction::Move { group, .. }
+                | TriggerAction::Rotate { group, .. }
+                | TriggerAction::Scale { group, .. }
+                | TriggerAction::Toggle { group, .. } => {
+                    if !object_groups.contains(group) {
+                        problems.push(format!("Trigger at ({}, {}) targets group {}, which has no objects.", at.x, at.y, group));
+                    }
+                }
+                TriggerAction::Spawn { group, .. } => {
+                    if !self.triggers.iter().any(|other| other.in_group(*group)) {
+                        problems.push(format!("Trigger at ({}, {}) spawns group {}, which has no triggers.", at.x, at.y, group));
+                    }
+                }
+                TriggerAction::Count { item, group, .. } => {
+                    if !self.triggers.iter().any(|other| other.in_group(*group)) {
+                        problems.push(format!(
+                            "Count trigger at ({}, {}) for item {} targets group {}, which has no triggers.",
+                            at.x, at.y, item, group
+                        ));
+                    }
+                }
+                TriggerAction::Color { channel, .. } => {
+                    if !channel_exists(*channel) {
+                        problems.push(format!("Trigger at ({}, {}) fades color channel {}, which is not defined.", at.x, at.y, channel));
+                    }
+                }
+                TriggerAction::Pickup { .. } => {}
+            }
+
+            if trigger.activation() == TriggerActivation::Collision {
+                match trigger.collision {
+                    Some(condition) => {
+                        for block in [condition.block_a, condition.block_b] {
+                            if !block_ids.contains(&block) {
+                                problems.push(format!("Trigger at ({}, {}) watches collision block {}, which does not exist.", at.x, at.y, block));
+                            }
+                        }
+                    }
+                    None => problems.push(format!("Trigger at ({}, {}) is activated by collisions but names no blocks.", at.x, at.y)),
+                }
+            }
+        }
+
+        if let Some(music) = &self.music {
+            if !Path::new("assets").join(music).exists() {
+                problems.push(format!("Music file {} does not exist.", music));
+            }
+        }
+
+        problems
+    }
+
+    pub fn add_object(&mut self, object: LevelObject) {
+        match object {
+            LevelObject::Obstacle(data) => self.obstacles.push(data),
+            LevelObject::Coin(data) => self.coins.push(data),
+            LevelObject::Sawblade(data) => self.sawblades.push(data),
+            LevelObject::Trigger(data) => self.triggers.push(data),
+            LevelObject::StartPosition(data) => self.start_positions.push(data),
+        }
+    }
+}
+
+// Any single object of a level, so tools like the editor can handle them alike
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LevelObject {
+    Obstacle(ObstacleData),
+    Coin(CoinData),
+    Sawblade(SawbladeData),
+    Trigger(TriggerData),
+    StartPosition(StartPositionData),
+}
+
+impl LevelObject {
+    pub fn position(&self) -> Vec2 {
+        match self {
+            LevelObject::Obstacle(data) => data.position,
+            LevelObject::Coin(data) => data.position,
+            LevelObject::Sawblade(data) => data.position,
+            LevelObject::Trigger(data) => data.position,
+            LevelObject::StartPosition(data) => data.position,
+        }
+    }
+
+    pub fn set_position(&mut self, position: Vec2) {
+        match self {
+            LevelObject::Obstacle(data) => data.position = position,
+            LevelObject::Coin(data) => data.position = position,
+            LevelObject::Sawblade(data) => data.position = position,
+            LevelObject::Trigger(data) => data.position = position,
+            LevelObject::StartPosition(data) => data.position = position,
+        }
+    }
+}
+
+// Alternative spawn point used to playtest the middle or end of a level
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StartPositionData {
+    pub position: Vec2,
+    pub speed: Option<f32>,
+    pub game_mode: Option<GameMode>,
+    pub gravity_flipped: Option<bool>,
+    pub mini: Option<bool>,
+}
+
+// Secret coin, identified by its index in the level's coin list
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CoinData {
+    pub position: Vec2,
+}
+
+// Spinning circular hazard
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SawbladeData {
+    pub position: Vec2,
+    pub radius: f32,
+    pub rotation_speed: Option<f32>, // Degrees per second clockwise, defaults to one turn per second
+    pub hitbox_shrink: Option<f32>, // Fraction of the radius that does not hurt, defaults to 0.2
+    pub color: Option<[f32; 3]>,
+    pub groups: Option<Vec<u32>>,
+}
+
+impl SawbladeData {
+    pub fn rotation_speed(&self) -> f32 {
+        self.rotation_speed.unwrap_or(360.0)
+    }
+
+    pub fn hitbox_radius(&self) -> f32 {
+        self.radius * (1.0 - self.hitbox_shrink.unwrap_or(0.2).clamp(0.0, 1.0))
+    }
+}
+
+// Invisible object that fires its action when activated
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TriggerData {
+    pub position: Vec2,
+    pub action: TriggerAction,
+    pub activation: Option<TriggerActivation>,
+    pub groups: Option<Vec<u32>>, // Lets spawn and count triggers target this trigger
+    pub size: Option<Vec2>, // Touch area centered on the position, defaults to one block
+    pub collision: Option<CollisionCondition>, // Block pair watched by collision activation
+}
+
+impl TriggerData {
+    pub fn activation(&self) -> TriggerActivation {
+        self.activation.unwrap_or_default()
+    }
+
+    pub fn in_group(&self, group: u32) -> bool {
+        self.groups.as_ref().is_some_and(|groups| groups.contains(&group))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerActivation {
+    // Fires when the player's x passes the trigger
+    #[default]
+    Position,
+    // Fires when the player touches the trigger's area
+    Touch,
+    // Only fires when a spawn or count trigger targets one of its groups
+    Spawn,
+    // Fires each time the collision blocks in `collision` start or stop overlapping
+    Collision,
+}
+
+// Pair of collision block IDs a collision trigger watches, in either order
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct CollisionCondition {
+    pub block_a: u32,
+    pub block_b: u32,
+    pub on_exit: Option<bool>, // Fire when the blocks stop overlapping instead
+}
+
+impl CollisionCondition {
+    pub fn matches(&self, a: u32, b: u32, started: bool) -> bool {
+        let pair = (a == self.block_a && b == self.block_b) || (a == self.block_b && b == self.block_a);
+        pair && self.on_exit.unwrap_or(false) != started
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerAction {
+    Move {
+        group: u32,
+        offset: Vec2,
+        duration: f32,
+        easing: Option<Easing>,
+    },
+    Rotate {
+        group: u32,
+        degrees: f32,
+        duration: f32,
+        easing: Option<Easing>,
+    },
+    Scale {
+        group: u32,
+        scale: Vec2,
+        duration: f32,
+        easing: Option<Easing>,
+    },
+    Color {
+        channel: u32,
+        color: [f32; 3],
+        alpha: Option<f32>,
+        duration: f32,
+        easing: Option<Easing>,
+    },
+    // Shows or hides a group and enables or disables its colliders
+    Toggle {
+        group: u32,
+        enabled: bool,
+    },
+    // Fires the spawn-activated triggers of a group after a delay
+    Spawn {
+        group: u32,
+        delay: f32,
+    },
+    // Adds to an item counter
+    Pickup {
+        item: u32,
+        amount: i32,
+    },
+    // Once fired, spawns a group as soon as an item counter reaches the target
+    Count {
+        item: u32,
+        target: i32,
+        group: u32,
+    },
+}
+
+impl TriggerAction {
+    pub fn duration(&self) -> f32 {
+        match self {
+            TriggerAction::Move { duration, .. }
+            | TriggerAction::Rotate { duration, .. }
+            | TriggerAction::Scale { duration, .. }
+            | TriggerAction::Color { duration, .. } => *duration,
+            _ => 0.0,
+        }
+    }
+
+    pub fn easing(&self) -> Easing {
+        match self {
+            TriggerAction::Move { easing, .. }
+            | TriggerAction::Rotate { easing, .. }
+            | TriggerAction::Scale { easing, .. }
+            | TriggerAction::Color { easing, .. } => easing.unwrap_or_default(),
+            _ => Easing::Linear,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    BounceOut,
+}
+
+impl Easing {
+    // Maps linear progress in [0, 1] onto the easing curve
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::BounceOut => {
+                let n = 7.5625;
+                let d = 2.75;
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+// IDs of every level file in the levels folder, in ascending order
+pub fn list_level_ids() -> Vec<usize> {
+    let Ok(entries) = fs::read_dir("assets/levels") else {
+        warn!("Failed to read the levels folder.");
+        return Vec::new();
+    };
+    let mut ids = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| level_id_from_file_name(&entry.file_name().into_string().ok()?))
+        .collect::<Vec<usize>>();
+    ids.sort();
+    ids
+}
+
+// File a level is stored in
+pub fn level_path(level_id: usize) -> String {
+    format!("assets/levels/level_{}.json", level_id)
+}
+
+fn level_id_from_file_name(name: &str) -> Option<usize> {
+    name.strip_prefix("level_")?.strip_suffix(".json")?.parse().ok()
+}
+
+// Level ID typed as a number or as the level's file, like "12", "level_12.json" or
+// "assets/levels/level_12.json"
+pub fn parse_level_id(value: &str) -> Option<usize> {
+    let value = value.trim();
+    let name = value.rsplit(['/', '\\']).next().unwrap_or(value);
+    name.parse().ok().or_else(|| level_id_from_file_name(name))
+}
+
+pub fn save_level(level: &Level) -> std::io::Result<()> {
+    save_level_to(level, &level_path(level.level_id))
+}
+
+fn save_level_to(level: &Level, path: &str) -> std::io::Result<()> {
+    let serialized_level = serde_json::to_string_pretty(level).expect("Failed to serialize level");
+    fs::write(path, serialized_level)
 }
 
 // Example level loading function
 pub fn load_level(level_id: usize) -> Option<Level> {
-    let path = format!("assets/levels/level_{}.json", level_id);
-    // let path = format!("assets/levels/level_edited.json");
+    load_level_from(&level_path(level_id))
+}
+
+fn load_level_from(path: &str) -> Option<Level> {
     info!("Attempting to load level file: {}", path);
 
-    let data = match fs::read_to_string(&path) {
+    let data = match fs::read_to_string(path) {
         Ok(content) => content,
         Err(e) => {
             warn!("Failed to read level file: {}. Error: {}", path, e);
@@ -40,3 +567,112 @@ pub fn load_level(level_id: usize) -> Option<Level> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(position: Vec2) -> ObstacleData {
+        ObstacleData {
+            position,
+            vertices: [[0.0, 0.0], [30.0, 0.0], [30.0, 30.0], [0.0, 30.0]],
+            ..default()
+        }
+    }
+
+    fn trigger(action: TriggerAction) -> TriggerData {
+        TriggerData { position: Vec2::new(90.0, 0.0), action, activation: None, groups: None, size: None, collision: None }
+    }
+
+    fn level() -> Level {
+        Level {
+            level_id: 7,
+            obstacles: vec![ObstacleData { is_finish: Some(true), ..block(Vec2::new(600.0, 0.0)) }],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn a_level_with_a_finish_has_no_problems() {
+        assert!(level().validate().is_empty());
+    }
+
+    #[test]
+    fn reports_a_missing_finish_and_flat_obstacles() {
+        let mut flat = block(Vec2::new(30.0, 0.0));
+        flat.vertices = [[0.0, 0.0], [30.0, 0.0], [60.0, 0.0], [0.0, 0.0]];
+        let level = Level { obstacles: vec![flat], ..level() };
+
+        assert_eq!(
+            level.validate(),
+            vec!["The level has no finish line.".to_string(), "Obstacle at (30, 0) has no area.".to_string()]
+        );
+    }
+
+    #[test]
+    fn reports_missing_color_channels_and_groups() {
+        let mut level = level();
+        level.obstacles.push(ObstacleData { color_channel: Some(5), ..block(Vec2::ZERO) });
+        level.triggers.push(trigger(TriggerAction::Toggle { group: 3, enabled: false }));
+        level.triggers.push(trigger(TriggerAction::Spawn { group: 4, delay: 0.0 }));
+        level.triggers.push(trigger(TriggerAction::Count { item: 1, target: 2, group: 4 }));
+
+        assert_eq!(
+            level.validate(),
+            vec![
+                "Obstacle at (0, 0) uses color channel 5, which is not defined.".to_string(),
+                "Trigger at (90, 0) targets group 3, which has no objects.".to_string(),
+                "Trigger at (90, 0) spawns group 4, which has no triggers.".to_string(),
+                "Count trigger at (90, 0) for item 1 targets group 4, which has no triggers.".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_collision_triggers_without_their_blocks() {
+        let mut level = level();
+        level.obstacles.push(ObstacleData { collision_block: Some(1), ..block(Vec2::ZERO) });
+        let mut watcher = trigger(TriggerAction::Pickup { item: 1, amount: 1 });
+        watcher.activation = Some(TriggerActivation::Collision);
+        watcher.collision = Some(CollisionCondition { block_a: 1, block_b: 2, on_exit: None });
+        level.triggers.push(watcher);
+
+        assert_eq!(level.validate(), vec!["Trigger at (90, 0) watches collision block 2, which does not exist.".to_string()]);
+    }
+
+    #[test]
+    fn saved_levels_load_back_unchanged() {
+        let mut level = level();
+        level.music = Some("audio/level.ogg".to_string());
+        level.platformer = Some(true);
+        level.add_object(LevelObject::Obstacle(ObstacleData { groups: Some(vec![2]), ..block(Vec2::new(60.0, 0.0)) }));
+        level.add_object(LevelObject::Coin(CoinData { position: Vec2::new(120.0, 60.0) }));
+        level.add_object(LevelObject::Trigger(trigger(TriggerAction::Toggle { group: 2, enabled: false })));
+        level.add_object(LevelObject::StartPosition(StartPositionData {
+            position: Vec2::new(300.0, 6.0),
+            speed: Some(250.0),
+            game_mode: Some(GameMode::Robot),
+            gravity_flipped: None,
+            mini: Some(true),
+        }));
+
+        let path = std::env::temp_dir().join(format!("level_round_trip_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        save_level_to(&level, path).unwrap();
+        let loaded = load_level_from(path);
+        let _ = fs::remove_file(path);
+
+        let loaded = loaded.expect("the saved level loads");
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&level).unwrap());
+    }
+
+    #[test]
+    fn level_ids_can_be_typed_as_files() {
+        assert_eq!(parse_level_id("12"), Some(12));
+        assert_eq!(parse_level_id(" level_12.json "), Some(12));
+        assert_eq!(parse_level_id("assets/levels/level_3.json"), Some(3));
+        assert_eq!(parse_level_id("assets\\levels\\level_3.json"), Some(3));
+        assert_eq!(parse_level_id("level_3"), None);
+        assert_eq!(parse_level_id("intro.json"), None);
+    }
+}
@@ -3,6 +3,7 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use bevy::log::*;
 use editor::EditorPlugin;
+use triggers::TriggerPlugin;
 
 mod components;
 mod levels;
@@ -10,18 +11,20 @@ mod states;
 mod systems;
 mod ui;
 mod editor;
+mod triggers;
 
 use components::SelectedLevel;
 use states::GameState;
 use systems::gameplay::{cleanup_gameplay, setup_gameplay};
 use ui::{
-    button_system, cleanup_level_selection, cleanup_title_screen, level_button_system,
-    setup_level_selection, setup_title_screen,
+    button_system, cleanup_level_selection, cleanup_title_screen, editor_level_button_system, level_button_system,
+    setup_editor_level_selection, setup_level_selection, setup_title_screen, start_position_button_system,
 };
-use crate::systems::gameplay::{collision_event_system, continuous_floor_system, exit_level_system, level_scrolling_system, player_movement_system, spawn_floor};
+use crate::systems::gameplay::{coin_collection_system, collision_event_system, slope_system, continuous_floor_system, exit_level_system, level_scrolling_system, player_animation_system, player_movement_system, portal_system, spawn_floor, spider_streak_system, dash_orb_system, jump_pad_system, teleport_portal_system, sawblade_rotation_system, proximity_fade_system, level_end_system, particle_system, death_sequence_system, restart_level_system};
 use bevy::log::LogPlugin;
-use crate::components::LevelProgress;
-use crate::systems::{play_death_sound, play_victory_sound, progress_tracker_system, setup_audio_system};
+use bevy::sprite::Material2dPlugin;
+use crate::components::{BlendingColorMaterial, DeathSettings, SelectedEditorLevel, JumpSettings, LevelEnd, PlayerDeath, LevelProgress, PlatformerSettings, PlayerTeleported, RunCoins};
+use crate::systems::{play_death_sound, play_victory_sound, progress_tracker_system, record_collected_coins, record_platformer_completion, setup_audio_system};
 use crate::ui::{cleanup_game_over_menu, cleanup_leaderboard_screen, cleanup_victory_screen, game_over_menu_buttons, setup_game_over_menu, setup_leaderboard_screen, setup_victory_screen, update_progress_ui, victory_screen_buttons};
 
 fn main() {
@@ -43,10 +46,18 @@ fn main() {
                          .set(AudioPlugin::default()) // Added AudioPlugin here
         )
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugins(EditorPlugin)
+        .add_plugins(Material2dPlugin::<BlendingColorMaterial>::default())
+        .add_plugins(EditorPlugin::default())
+        .add_plugins(TriggerPlugin)
         .init_state::<GameState>()
         .insert_resource(SelectedLevel::default())
+        .insert_resource(SelectedEditorLevel::default())
         .insert_resource(LevelProgress::default())
+        .insert_resource(RunCoins::default())
+        .insert_resource(JumpSettings::default())
+        .insert_resource(PlatformerSettings::default())
+        .insert_resource(DeathSettings::default())
+        .add_event::<PlayerTeleported>()
         // Audio Systems
         .add_systems(Startup, setup_audio_system)
         .add_systems(Update, (play_death_sound, play_victory_sound).run_if(in_state(GameState::Playing)))
@@ -56,18 +67,46 @@ fn main() {
         .add_systems(OnExit(GameState::TitleScreen), cleanup_title_screen)
         // Level Selection Systems
         .add_systems(OnEnter(GameState::LevelSelection), setup_level_selection)
-        .add_systems(Update, level_button_system.run_if(in_state(GameState::LevelSelection)))
+        .add_systems(Update, (level_button_system, start_position_button_system).run_if(in_state(GameState::LevelSelection)))
         .add_systems(OnExit(GameState::LevelSelection), cleanup_level_selection)
+        // Editor Level Selection Systems
+        .add_systems(OnEnter(GameState::EditorLevelSelection), setup_editor_level_selection)
+        .add_systems(Update, editor_level_button_system.run_if(in_state(GameState::EditorLevelSelection)))
+        .add_systems(OnExit(GameState::EditorLevelSelection), cleanup_level_selection)
         // Gameplay Systems
         .add_systems(OnEnter(GameState::Playing), (setup_gameplay, spawn_floor))
         .add_systems(OnExit(GameState::Playing), cleanup_gameplay)
+        .add_systems(OnEnter(GameState::Restarting), restart_level_system)
         .add_systems(
             Update,
             (
-                player_movement_system,
-                collision_event_system,
-                level_scrolling_system,
-                continuous_floor_system,
+                // Input, hazards and the camera stop once the level end or death sequence runs
+                (
+                    slope_system.before(player_movement_system).before(collision_event_system),
+                    player_movement_system,
+                    portal_system,
+                    dash_orb_system.after(player_movement_system),
+                    jump_pad_system.after(player_movement_system),
+                    teleport_portal_system.before(level_scrolling_system),
+                    collision_event_system,
+                    coin_collection_system,
+                    level_scrolling_system,
+                )
+                    .run_if(not(resource_exists::<LevelEnd>).and_then(not(resource_exists::<PlayerDeath>))),
+                // The run is recorded on contact with the finish line, leaving during the end sequence keeps it
+                (record_collected_coins, record_platformer_completion).run_if(resource_added::<LevelEnd>),
+                level_end_system.run_if(resource_exists::<LevelEnd>),
+                death_sequence_system.run_if(resource_exists::<PlayerDeath>),
+                // The world freezes while the player dies
+                (
+                    player_animation_system.after(player_movement_system),
+                    sawblade_rotation_system,
+                    continuous_floor_system,
+                )
+                    .run_if(not(resource_exists::<PlayerDeath>)),
+                particle_system,
+                spider_streak_system,
+                proximity_fade_system,
                 exit_level_system,
             )
                 .run_if(in_state(GameState::Playing)),
@@ -8,6 +8,10 @@ pub enum GameState {
     Playing,
     GameOver,
     Editor,
+    // Picks the level the editor opens
+    EditorLevelSelection,
     VictoryScreen,
     LeaderboardScreen,
+    // Passed through to run a level's exit and enter systems again
+    Restarting,
 }
\ No newline at end of file
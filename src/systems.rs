@@ -5,8 +5,9 @@ use bevy::render::mesh::{Mesh, PrimitiveTopology};
 use bevy::render::render_asset::RenderAssetUsages;
 use bevy::sprite::MaterialMesh2dBundle;
 use bevy::audio::*;
-use crate::components::{GameplayCamera, Obstacle, Player, Floor, NonLethal, SelectedLevel, FinishLine, LevelProgress, DeathSound, VictorySound, GameAudio, ProgressHistory, ProgressEntry};
-use crate::levels::load_level;
+use crate::components::{GameplayCamera, Obstacle, Player, Floor, NonLethal, SelectedLevel, FinishLine, LevelProgress, DeathSound, VictorySound, GameAudio, ProgressHistory, ProgressEntry, PlayerState, Coin, RunCoins, CoinRecord, GroupMember, Trigger, TriggerDispatch, LevelClock, ColorChannels, BlendingColorMaterial, GroundLine, JumpBuffer, JumpSettings, Slope, SlopeContact, PlatformerSettings, GameMode, GameModePortal, PlayerVisual, RobotBoost, SpiderStreak, DashOrb, TeleportPortal, PlayerTeleported, OrbContact, Dash, JumpPad, Sawblade, ProximityFade, CollisionBlock, LevelEnd, LevelMusic, Particle, PlayerDeath, DeathSettings, PlaytestLevel};
+use crate::levels::{ColorChannelData, ObstacleData, BACKGROUND_CHANNEL};
+use crate::levels::{load_level, PLAYER_START};
 use crate::states::GameState;
 use chrono::Local;
 
@@ -14,14 +15,25 @@ pub mod gameplay {
     use crate::components::{DeathSound, ProgressText, VictorySound};
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn setup_gameplay(
         mut commands: Commands,
         mut materials: ResMut<Assets<ColorMaterial>>,
+        mut blending_materials: ResMut<Assets<BlendingColorMaterial>>,
         mut meshes: ResMut<Assets<Mesh>>,
         selected_level: Res<SelectedLevel>,
+        playtest: Option<Res<PlaytestLevel>>,
+        mut progress: ResMut<LevelProgress>,
+        mut run_coins: ResMut<RunCoins>,
+        jump_settings: Res<JumpSettings>,
         asset_server: Res<AssetServer>,
     ) {
         info!("Setting up gameplay for level {}", selected_level.level_id);
+        progress.current_percentage = 0.0;
+        progress.from_start_position = false;
+        progress.elapsed_time = 0.0;
+        run_coins.collected.clear();
+        commands.insert_resource(LevelClock::default());
 
         // Spawn the gameplay camera
         commands.spawn((
@@ -30,53 +42,173 @@ pub mod gameplay {
         ));
         debug!("Gameplay camera spawned.");
 
-        // Load and set up the selected level
-        if let Some(level) = load_level(selected_level.level_id) {
+        // Load and set up the selected level, or the level the editor is playtesting
+        let level = match playtest.as_deref() {
+            Some(playtest) => Some(playtest.level.clone()),
+            None => load_level(selected_level.level_id),
+        };
+        if let Some(level) = level {
             info!("Level {} loaded successfully.", level.level_id);
 
+            // Create one shared material per color channel
+            let mut color_channels = ColorChannels::default();
+            for channel_data in level.color_channels_with_defaults().iter() {
+                color_channels.add_channel(channel_data, &mut materials, &mut blending_materials);
+            }
+
             // Spawn obstacles
             for obstacle_data in level.obstacles.iter() {
-                let mut mesh = Mesh::new(
-                    PrimitiveTopology::TriangleList,
-                    RenderAssetUsages::default()
-                );
+                let shape = obstacle_data.shape();
+                let mesh = obstacle_mesh(shape);
 
-                let positions = obstacle_data
-                    .vertices
-                    .iter()
-                    .map(|&[x, y]| [x, y, 0.0])
-                    .collect::<Vec<_>>();
-                mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+                let transform = Transform::from_translation(Vec3::new(
+                    obstacle_data.position.x,
+                    obstacle_data.position.y,
+                    0.0,
+                ));
 
-                let indices = vec![0, 1, 2, 2, 3, 0];
-                mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
+                // Grouped objects can be animated by triggers, so their colliders must follow the transform
+                let groups = obstacle_data.groups.clone().unwrap_or_default();
+                let rigid_body = if groups.is_empty() {
+                    RigidBody::Fixed
+                } else {
+                    RigidBody::KinematicPositionBased
+                };
 
-                let color = obstacle_data.color.unwrap_or([0.8, 0.2, 0.2]);
-                let material = materials.add(ColorMaterial::from(Color::srgb(color[0], color[1], color[2])));
+                let mesh = meshes.add(mesh);
 
-                let mut entity = commands.spawn((
-                    MaterialMesh2dBundle {
-                        mesh: meshes.add(mesh).into(),
-                        material,
-                        transform: Transform::from_translation(Vec3::new(
-                            obstacle_data.position.x,
-                            obstacle_data.position.y,
-                            0.0,
-                        )),
+                // The exit of a teleport pair is only drawn, the entrance does the teleporting
+                if let Some(teleport) = obstacle_data.teleport {
+                    commands.spawn(MaterialMesh2dBundle {
+                        mesh: mesh.clone().into(),
+                        material: materials.add(ColorMaterial::from(Color::srgba(1.0, 0.6, 0.1, 0.6))),
+                        transform: Transform::from_translation(teleport.exit.extend(0.0)),
                         ..default()
-                    },
-                    Obstacle,
-                    RigidBody::Fixed,
-                    Collider::polyline(
-                        obstacle_data.vertices.iter().map(|&[x, y]| Vec2::new(x, y)).collect(),
-                        None,
-                    ),
-                ));
+                    });
+                }
+
+                let mut entity = commands.spawn(rigid_body);
+
+                // Portals, orbs, pads and collision blocks are passed through, so they are not obstacles
+                if obstacle_data.portal.is_some()
+                    || obstacle_data.dash_orb.is_some()
+                    || obstacle_data.pad.is_some()
+                    || obstacle_data.teleport.is_some()
+                    || obstacle_data.collision_block.is_some()
+                {
+                    let points = shape.iter().map(|&[x, y]| Vec2::new(x, y)).collect::<Vec<_>>();
+                    entity.insert((
+                        Collider::convex_hull(&points).unwrap_or_else(|| Collider::polyline(points, None)),
+                        Sensor,
+                    ));
+                    if let Some(mode) = obstacle_data.portal {
+                        entity.insert(GameModePortal(mode));
+                    }
+                    if let Some(dash_orb) = obstacle_data.dash_orb {
+                        entity.insert(DashOrb { angle: dash_orb.angle, speed: dash_orb.speed });
+                    }
+                    if let Some(teleport) = obstacle_data.teleport {
+                        entity.insert(TeleportPortal { exit: teleport.exit });
+                    }
+                    if let Some(velocity) = obstacle_data.pad {
+                        entity.insert(JumpPad { velocity });
+                    }
+                    if let Some(id) = obstacle_data.collision_block {
+                        // Blocks are mostly fixed or kinematic, which Rapier skips by default
+                        entity.insert((
+                            CollisionBlock { id },
+                            ActiveEvents::COLLISION_EVENTS,
+                            ActiveCollisionTypes::all(),
+                        ));
+                    }
+                } else if let Some(slope) = obstacle_data.slope {
+                    // Slopes hold the player up themselves, their collider only detects hits from below
+                    let [_, [width, _], _, _] = shape;
+                    let rise = width * slope.angle.gradient();
+                    let rise = if slope.descending.unwrap_or(false) { -rise } else { rise };
+                    let peak = if rise >= 0.0 { Vec2::new(width, rise) } else { Vec2::new(0.0, -rise) };
+                    entity.insert((
+                        Obstacle,
+                        Slope { width, rise },
+                        Collider::triangle(Vec2::ZERO, Vec2::new(width, 0.0), peak),
+                        Sensor,
+                    ));
+                } else if !obstacle_data.decoration.unwrap_or(false) {
+                    // Everything else is solid, decorations are only drawn
+                    entity.insert((
+                        Obstacle,
+                        Collider::polyline(
+                            shape.iter().map(|&[x, y]| Vec2::new(x, y)).collect(),
+                            None,
+                        ),
+                    ));
+                }
+
+                let own_color = match obstacle_data.color {
+                    Some(color) => Color::srgb(color[0], color[1], color[2]),
+                    None => default_obstacle_color(obstacle_data),
+                };
+                if let Some(channel_id) = obstacle_data.color_channel {
+                    if !color_channels.channels.contains_key(&channel_id) {
+                        warn!("Color channel {} is not defined. Defaulting to white.", channel_id);
+                        let channel_data = ColorChannelData { id: channel_id, color: [1.0, 1.0, 1.0], alpha: None, blending: None };
+                        color_channels.add_channel(&channel_data, &mut materials, &mut blending_materials);
+                    }
+                }
+
+                // Objects on a color channel share its material, others get their own color.
+                // Fading objects always need their own, but keep following their channel.
+                // Collision blocks are never drawn.
+                if obstacle_data.collision_block.is_some() {
+                    entity.insert((TransformBundle::from(transform), VisibilityBundle::default()));
+                } else if let Some(fade) = obstacle_data.fade {
+                    let color = obstacle_data.color_channel
+                        .and_then(|channel_id| color_channels.color(channel_id))
+                        .unwrap_or(own_color);
+                    let center = shape.iter().fold(Vec2::ZERO, |sum, &[x, y]| sum + Vec2::new(x, y)) / 4.0;
+                    entity.insert((
+                        MaterialMesh2dBundle {
+                            mesh: mesh.into(),
+                            material: materials.add(ColorMaterial::from(color)),
+                            transform,
+                            ..default()
+                        },
+                        ProximityFade { data: fade, center, channel: obstacle_data.color_channel, color: own_color },
+                    ));
+                } else if let Some(channel_id) = obstacle_data.color_channel {
+                    let channel = &color_channels.channels[&channel_id];
+                    if channel.blending {
+                        entity.insert(MaterialMesh2dBundle {
+                            mesh: mesh.into(),
+                            material: channel.blending_material.clone(),
+                            transform,
+                            ..default()
+                        });
+                    } else {
+                        entity.insert(MaterialMesh2dBundle {
+                            mesh: mesh.into(),
+                            material: channel.material.clone(),
+                            transform,
+                            ..default()
+                        });
+                    }
+                } else {
+                    entity.insert(MaterialMesh2dBundle {
+                        mesh: mesh.into(),
+                        material: materials.add(ColorMaterial::from(own_color)),
+                        transform,
+                        ..default()
+                    });
+                }
 
                 if obstacle_data.non_lethal.unwrap_or(false) {
                     entity.insert(NonLethal);
                 }
 
+                if !groups.is_empty() {
+                    entity.insert(GroupMember { groups, base: transform });
+                }
+
                 if obstacle_data.is_finish.unwrap_or(false) {
                     info!("Finish line obstacle found at position: {:?}", obstacle_data.position);
                     entity.insert(FinishLine);
@@ -84,31 +216,147 @@ pub mod gameplay {
                 }
             }
 
+            // Spawn sawblades. The disc spins as a child, so triggers can still move the parent.
+            for sawblade_data in level.sawblades.iter() {
+                let transform = Transform::from_translation(sawblade_data.position.extend(0.0));
+                let groups = sawblade_data.groups.clone().unwrap_or_default();
+                let rigid_body = if groups.is_empty() {
+                    RigidBody::Fixed
+                } else {
+                    RigidBody::KinematicPositionBased
+                };
+                let color = sawblade_data.color.map_or(Color::srgb(0.7, 0.7, 0.75), |color| Color::srgb(color[0], color[1], color[2]));
+
+                let mut entity = commands.spawn((
+                    rigid_body,
+                    Obstacle,
+                    Collider::ball(sawblade_data.hitbox_radius()),
+                    Sensor,
+                    TransformBundle::from(transform),
+                    VisibilityBundle::default(),
+                ));
+                if !groups.is_empty() {
+                    entity.insert(GroupMember { groups, base: transform });
+                }
+                entity.with_children(|parent| {
+                    parent.spawn((
+                        MaterialMesh2dBundle {
+                            mesh: meshes.add(sawblade_mesh(sawblade_data.radius)).into(),
+                            material: materials.add(ColorMaterial::from(color)),
+                            ..default()
+                        },
+                        Sawblade { rotation_speed: sawblade_data.rotation_speed() },
+                    ));
+                });
+            }
+
+            // Spawn coins
+            let coin_mesh = meshes.add(Circle::new(10.0));
+            let coin_material = materials.add(ColorMaterial::from(Color::srgb(1.0, 0.85, 0.1)));
+            for (index, coin_data) in level.coins.iter().enumerate() {
+                commands.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: coin_mesh.clone().into(),
+                        material: coin_material.clone(),
+                        transform: Transform::from_translation(coin_data.position.extend(0.0)),
+                        ..default()
+                    },
+                    Coin { index },
+                    Collider::ball(10.0),
+                    Sensor,
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+            }
+
+            // Pick the spawn point, either the level start or a start position
+            let mut player_state = PlayerState {
+                platformer: level.platformer.unwrap_or(false),
+                ..default()
+            };
+            progress.platformer = player_state.platformer;
+            let mut spawn_position = PLAYER_START;
+            if let Some(playtest) = playtest.as_deref() {
+                // Playtests are never recorded, wherever they start
+                progress.from_start_position = true;
+                if let Some(start_x) = playtest.start_x {
+                    info!("Playtesting level {} from x {}", level.level_id, start_x);
+                    spawn_position.x = start_x;
+                }
+            } else if let Some(index) = selected_level.start_position {
+                if let Some(start) = level.start_positions.get(index) {
+                    info!("Starting level {} from start position {} at {:?}", level.level_id, index, start.position);
+                    spawn_position = start.position;
+                    player_state.speed = start.speed.unwrap_or(player_state.speed);
+                    player_state.game_mode = start.game_mode.unwrap_or_default();
+                    player_state.gravity_flipped = start.gravity_flipped.unwrap_or(false);
+                    player_state.mini = start.mini.unwrap_or(false);
+                    progress.from_start_position = true;
+                } else {
+                    warn!("Start position {} not found in level {}. Starting from the beginning.", index, level.level_id);
+                }
+            }
+            let player_size = player_state.size();
+
+            // Spawn triggers. Position triggers behind the spawn point count as already
+            // finished so a run from a start position sees the same state as a full run.
+            commands.insert_resource(TriggerDispatch { start_x: spawn_position.x, ..default() });
+            for (index, trigger_data) in level.triggers.iter().enumerate() {
+                commands.spawn((
+                    Trigger { index, data: trigger_data.clone(), fire_times: Vec::new() },
+                    TransformBundle::from(Transform::from_translation(trigger_data.position.extend(0.0))),
+                ));
+            }
+
+            if let Some(background) = color_channels.color(BACKGROUND_CHANNEL) {
+                commands.insert_resource(ClearColor(background));
+            }
+            commands.insert_resource(color_channels);
+
             // Spawn the player
             commands
                 .spawn(RigidBody::Dynamic)
-                .insert(GravityScale(75.0))
-                .insert(
-                    SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::srgb(0.0, 0.0, 1.0),
-                            custom_size: Some(Vec2::new(30.0, 30.0)),
-                            ..Default::default()
+                .insert(GravityScale(player_state.gravity_scale()))
+                .insert(TransformBundle::from(Transform::from_translation(spawn_position.extend(0.0))))
+                .insert(VisibilityBundle::default())
+                .with_children(|player| {
+                    player.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: player_state.game_mode.color(),
+                                custom_size: Some(player_size),
+                                ..Default::default()
+                            },
+                            ..default()
                         },
-                        ..default()
-                    },
-                )
-                .insert(TransformBundle::from(Transform::from_xyz(-200.0, 6.0, 0.0)))
+                        PlayerVisual,
+                    ));
+                })
                 .insert(Velocity {
                     linvel: Vec2::new(1.0, 2.0),
                     angvel: 0.0,
                 })
                 .insert(Player)
-                .insert(Collider::cuboid(15.0, 15.0))
+                .insert(player_state)
+                .insert(JumpBuffer::new(&jump_settings))
+                .insert(SlopeContact::default())
+                .insert(RobotBoost::default())
+                .insert(OrbContact::default())
+                .insert(Dash::default())
+                .insert(Collider::cuboid(player_size.x / 2.0, player_size.y / 2.0))
                 .insert(ActiveEvents::COLLISION_EVENTS)
                 .insert(Sleeping::disabled())
                 .insert(Ccd::enabled())
                 .insert(LockedAxes::ROTATION_LOCKED);
+
+            if let Some(music) = level.music.as_ref() {
+                commands.spawn((
+                    AudioBundle {
+                        source: asset_server.load(music.clone()),
+                        settings: PlaybackSettings::LOOP,
+                    },
+                    LevelMusic,
+                ));
+            }
         }
         // Progess text
         commands.spawn((
@@ -126,40 +374,312 @@ pub mod gameplay {
     }
 
 
+    // Quad of an obstacle's four vertices, triangles and slopes repeat a vertex
+    pub fn obstacle_mesh(shape: [[f32; 2]; 4]) -> Mesh {
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default()
+        );
+
+        let positions = shape
+            .iter()
+            .map(|&[x, y]| [x, y, 0.0])
+            .collect::<Vec<_>>();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+
+        let indices = vec![0, 1, 2, 2, 3, 0];
+        mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
+        mesh
+    }
+
+    // Disc with a ring of saw teeth around it, as a triangle fan around the center
+    pub fn sawblade_mesh(radius: f32) -> Mesh {
+        let teeth = ((radius / 6.0).round() as usize).clamp(6, 32);
+        let mut positions = vec![[0.0, 0.0, 0.0]];
+        for i in 0..teeth * 2 {
+            let angle = i as f32 * std::f32::consts::PI / teeth as f32;
+            // Tips alternate with the valleys between teeth
+            let distance = if i % 2 == 0 { radius } else { radius * 0.78 };
+            positions.push([angle.cos() * distance, angle.sin() * distance, 0.0]);
+        }
+
+        let ring = teeth as u32 * 2;
+        let mut indices = Vec::new();
+        for i in 0..ring {
+            indices.extend([0, i + 1, (i + 1) % ring + 1]);
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
+        mesh
+    }
+
+    // Spins sawblades from the level clock, so restarts show the same angle at the same time
+    pub fn sawblade_rotation_system(
+        clock: Res<LevelClock>,
+        mut query: Query<(&mut Transform, &Sawblade)>,
+    ) {
+        for (mut transform, sawblade) in query.iter_mut() {
+            transform.rotation = Quat::from_rotation_z(-sawblade.rotation_speed.to_radians() * clock.elapsed);
+        }
+    }
+
+    // Sets the opacity of fading objects from their horizontal distance to the player
+    pub fn proximity_fade_system(
+        player_query: Query<&Transform, With<Player>>,
+        fade_query: Query<(&Transform, &ProximityFade, &Handle<ColorMaterial>), Without<Player>>,
+        color_channels: Option<Res<ColorChannels>>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let Ok(player_transform) = player_query.get_single() else {
+            return;
+        };
+
+        for (transform, fade, material_handle) in fade_query.iter() {
+            let center_x = transform.translation.x + fade.center.x;
+            let distance = (player_transform.translation.x - center_x).abs();
+            let color = fade.channel
+                .zip(color_channels.as_ref())
+                .and_then(|(channel_id, channels)| channels.color(channel_id))
+                .unwrap_or(fade.color);
+            let color = color.with_alpha(color.alpha() * fade.data.opacity(distance).clamp(0.0, 1.0));
+
+            // get_mut marks the material changed and re-uploads it, only do that on a real change
+            if materials.get(material_handle).is_some_and(|material| material.color != color) {
+                if let Some(material) = materials.get_mut(material_handle) {
+                    material.color = color;
+                }
+            }
+        }
+    }
+
+    // Vertical speed the player leaves the level with
+    const LEVEL_END_RISE: f32 = 40.0;
+    // Horizontal acceleration while flying off
+    const LEVEL_END_ACCELERATION: f32 = 900.0;
+
+    // Plays the end sequence while input, hazards and the camera are switched off: the player
+    // flies off to the right leaving particles, the music fades out, then the victory screen shows
+    pub fn level_end_system(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut level_end: ResMut<LevelEnd>,
+        playtest: Option<Res<PlaytestLevel>>,
+        mut next_state: ResMut<NextState<GameState>>,
+        mut player_query: Query<(Entity, &Transform, &mut Velocity, &mut GravityScale, &PlayerState), With<Player>>,
+        music_query: Query<&AudioSink, With<LevelMusic>>,
+    ) {
+        if let Ok((entity, transform, mut velocity, mut gravity_scale, player_state)) = player_query.get_single_mut() {
+            if level_end.is_added() {
+                // Nothing can stop the player anymore
+                commands.entity(entity).insert(ColliderDisabled);
+                gravity_scale.0 = 0.0;
+                velocity.linvel = Vec2::new(velocity.linvel.x.max(player_state.speed), LEVEL_END_RISE);
+            }
+            velocity.linvel.x += LEVEL_END_ACCELERATION * time.delta_seconds();
+
+            // Trail of particles, spread by the time so no two look the same
+            let seed = time.elapsed_seconds() * 37.0;
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: player_state.game_mode.color(),
+                        custom_size: Some(Vec2::splat(6.0)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(transform.translation - Vec3::new(player_state.size().x / 2.0, 0.0, 0.1)),
+                    ..Default::default()
+                },
+                Particle {
+                    velocity: Vec2::new(-80.0 - 40.0 * seed.sin(), 60.0 * (seed * 1.7).cos()),
+                    timer: Timer::from_seconds(0.5, TimerMode::Once),
+                },
+            ));
+        }
+
+        level_end.timer.tick(time.delta());
+        for sink in music_query.iter() {
+            sink.set_volume(1.0 - level_end.timer.fraction());
+        }
+        if level_end.timer.finished() {
+            if playtest.is_some() {
+                next_state.set(GameState::Editor);
+            } else {
+                next_state.set(GameState::VictoryScreen);
+            }
+        }
+    }
+
+    // Number of pieces the player shatters into
+    const DEATH_PIECES: usize = 12;
+
+    // Plays the death sequence with the world frozen: the player shatters, the camera shakes
+    // and the death sound plays. After the configured delay the level restarts or GameOver opens.
+    #[allow(clippy::too_many_arguments)]
+    pub fn death_sequence_system(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut death: ResMut<PlayerDeath>,
+        death_settings: Res<DeathSettings>,
+        playtest: Option<Res<PlaytestLevel>>,
+        game_audio: Option<Res<GameAudio>>,
+        mut rapier_config: ResMut<RapierConfiguration>,
+        mut next_state: ResMut<NextState<GameState>>,
+        player_query: Query<(&Transform, &PlayerState, &Children), With<Player>>,
+        mut visual_query: Query<&mut Visibility, With<PlayerVisual>>,
+        mut camera_query: Query<&mut Transform, (With<GameplayCamera>, Without<Player>)>,
+    ) {
+        if death.is_added() {
+            info!("Player died.");
+            rapier_config.physics_pipeline_active = false;
+            if let Some(game_audio) = game_audio {
+                play_sound(&mut commands, game_audio.death_sound.clone());
+            }
+
+            if let Ok((transform, player_state, children)) = player_query.get_single() {
+                for &child in children.iter() {
+                    if let Ok(mut visibility) = visual_query.get_mut(child) {
+                        *visibility = Visibility::Hidden;
+                    }
+                }
+
+                // Pieces fly out in a ring from where the player was
+                let piece_size = player_state.size() / 3.0;
+                for i in 0..DEATH_PIECES {
+                    let angle = i as f32 / DEATH_PIECES as f32 * std::f32::consts::TAU;
+                    let speed = 120.0 + 60.0 * ((i * 7) % 5) as f32 / 4.0;
+                    commands.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: player_state.game_mode.color(),
+                                custom_size: Some(piece_size),
+                                ..Default::default()
+                            },
+                            transform: Transform::from_translation(transform.translation + Vec3::Z)
+                                .with_rotation(Quat::from_rotation_z(angle)),
+                            ..Default::default()
+                        },
+                        Particle {
+                            velocity: Vec2::from_angle(angle) * speed,
+                            timer: Timer::from_seconds(death_settings.delay.max(0.1), TimerMode::Once),
+                        },
+                    ));
+                }
+            }
+        }
+
+        death.timer.tick(time.delta());
+
+        // Shake the camera around where it stood, settling down over the shake time
+        for mut camera_transform in camera_query.iter_mut() {
+            let origin = *death.camera_origin.get_or_insert(camera_transform.translation);
+            let elapsed = death.timer.elapsed_secs();
+            let strength = (1.0 - elapsed / PlayerDeath::SHAKE_TIME).max(0.0) * 6.0;
+            let offset = Vec2::new((elapsed * 90.0).sin(), (elapsed * 77.0).cos()) * strength;
+            camera_transform.translation = origin + offset.extend(0.0);
+        }
+
+        if death.timer.finished() {
+            if playtest.is_some() {
+                next_state.set(GameState::Editor);
+            } else if death_settings.auto_restart {
+                next_state.set(GameState::Restarting);
+            } else {
+                next_state.set(GameState::GameOver);
+            }
+        }
+    }
+
+    pub fn particle_system(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut query: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+    ) {
+        for (entity, mut particle, mut transform, mut sprite) in query.iter_mut() {
+            particle.timer.tick(time.delta());
+            if particle.timer.finished() {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+            transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+            sprite.color.set_alpha(1.0 - particle.timer.fraction());
+        }
+    }
+
+    // Color of objects that neither set a color nor use a channel
+    pub fn default_obstacle_color(obstacle_data: &ObstacleData) -> Color {
+        if let Some(mode) = obstacle_data.portal {
+            mode.color().with_alpha(0.6)
+        } else if obstacle_data.dash_orb.is_some() {
+            Color::srgb(1.0, 0.2, 0.8)
+        } else if obstacle_data.pad.is_some() {
+            Color::srgb(1.0, 0.9, 0.1)
+        } else if obstacle_data.decoration.unwrap_or(false) {
+            Color::srgba(0.6, 0.6, 0.7, 0.5)
+        } else if obstacle_data.teleport.is_some() {
+            Color::srgba(0.1, 0.6, 1.0, 0.6)
+        } else {
+            Color::srgb(0.8, 0.2, 0.2)
+        }
+    }
+
     // Collision Event System
+    #[allow(clippy::too_many_arguments)]
     pub fn collision_event_system(
+        mut commands: Commands,
         mut collision_events: EventReader<CollisionEvent>,
-        mut next_state: ResMut<NextState<GameState>>,
+        mut progress: ResMut<LevelProgress>,
+        death_settings: Res<DeathSettings>,
         obstacle_query: Query<(Entity, &Transform), With<Obstacle>>,
         non_lethal_query: Query<Entity, With<NonLethal>>,
+        slope_query: Query<&Slope>,
         finish_query: Query<Entity, With<FinishLine>>,
-        player_query: Query<&Transform, With<Player>>,
+        player_query: Query<(Entity, &Transform, &PlayerState), With<Player>>,
     ) {
         for event in collision_events.read() {
             match event {
                 CollisionEvent::Started(e1, e2, _) => {
-                    if let Ok(player_transform) = player_query.get_single() {
-                        let (obstacle_entity, obstacle_transform) = if let Ok((entity, transform)) = obstacle_query.get(*e1) {
-                            (entity, transform)
-                        } else if let Ok((entity, transform)) = obstacle_query.get(*e2) {
-                            (entity, transform)
+                    if let Ok((player_entity, player_transform, player_state)) = player_query.get_single() {
+                        // Collision blocks report overlaps with obstacles too, only the player's contacts count here
+                        let other_entity = if *e1 == player_entity {
+                            *e2
+                        } else if *e2 == player_entity {
+                            *e1
                         } else {
                             continue;
                         };
+                        let Ok((obstacle_entity, obstacle_transform)) = obstacle_query.get(other_entity) else {
+                            continue;
+                        };
 
                         // Check if the collision is with the finish line
                         if finish_query.get(obstacle_entity).is_ok() {
-                            next_state.set(GameState::VictoryScreen);
+                            // The level counts as complete on contact, the end sequence is only for show
+                            info!("Finish line reached. Starting the level end sequence.");
+                            progress.current_percentage = 100.0;
+                            commands.insert_resource(LevelEnd::default());
+                            return;
+                        } else if let Ok(slope) = slope_query.get(obstacle_entity) {
+                            // Touching a slope from above, or from below with flipped gravity, is running on it.
+                            // Anything else is a crash.
+                            if !is_landing_on_slope(player_transform, player_state, obstacle_transform, slope) {
+                                commands.insert_resource(PlayerDeath::new(&death_settings));
+                                return;
+                            }
                         } else {
                             let is_non_lethal = non_lethal_query.get(obstacle_entity).is_ok();
-                            let player_size = Vec2::new(30.0, 30.0); // Assuming player size is 30x30
                             let obstacle_size = Vec2::new(25.0, 25.0); // Assuming obstacle size is 25x25
-                            if is_non_lethal && is_top_collision(player_transform, player_size, obstacle_transform, obstacle_size) {
-                                // Allow jumping on top of non-lethal obstacles
+                            if is_non_lethal && player_state.platformer {
+                                // Platformer players walk into walls, so non-lethal blocks are safe from any side
+                                continue;
+                            } else if is_non_lethal && is_landing_collision(player_transform, player_state, obstacle_transform, obstacle_size) {
+                                // Allow jumping on top of non-lethal obstacles, or under them with flipped gravity
                                 continue;
                             } else {
                                 // Handle lethal collision
-                                next_state.set(GameState::GameOver);
+                                commands.insert_resource(PlayerDeath::new(&death_settings));
+                                return;
                             }
                         }
                     } else {
@@ -177,18 +697,145 @@ pub mod gameplay {
         next_state.set(GameState::GameOver);
     }
 
+    pub fn coin_collection_system(
+        mut commands: Commands,
+        mut collision_events: EventReader<CollisionEvent>,
+        mut run_coins: ResMut<RunCoins>,
+        coin_query: Query<&Coin>,
+        player_query: Query<Entity, With<Player>>,
+    ) {
+        for event in collision_events.read() {
+            if let CollisionEvent::Started(e1, e2, _) = event {
+                let coin_entity = if player_query.contains(*e1) {
+                    *e2
+                } else if player_query.contains(*e2) {
+                    *e1
+                } else {
+                    continue;
+                };
+
+                if let Ok(coin) = coin_query.get(coin_entity) {
+                    if !run_coins.collected.contains(&coin.index) {
+                        info!("Coin {} collected.", coin.index);
+                        run_coins.collected.push(coin.index);
+                    }
+                    commands.entity(coin_entity).despawn_recursive();
+                }
+            }
+        }
+    }
+
+    // Deepest the player's bottom may sink into a slope and still count as being on top of it
+    const SLOPE_SNAP_DEPTH: f32 = 12.0;
+
+    pub fn is_above_slope(player_transform: &Transform, player_size: Vec2, slope_transform: &Transform, slope: &Slope) -> bool {
+        let player_bottom = player_transform.translation.y - player_size.y / 2.0;
+        let local_x = player_transform.translation.x - slope_transform.translation.x;
+        let surface = slope_transform.translation.y + slope.surface_height(local_x);
+        player_bottom >= surface - SLOPE_SNAP_DEPTH
+    }
+
+    // The flat underside of a slope is its surface under flipped gravity
+    pub fn is_below_slope(player_transform: &Transform, player_size: Vec2, slope_transform: &Transform) -> bool {
+        let player_top = player_transform.translation.y + player_size.y / 2.0;
+        player_top <= slope_transform.translation.y + SLOPE_SNAP_DEPTH
+    }
+
+    pub fn is_landing_on_slope(player_transform: &Transform, player_state: &PlayerState, slope_transform: &Transform, slope: &Slope) -> bool {
+        if player_state.gravity_flipped {
+            is_below_slope(player_transform, player_state.size(), slope_transform)
+        } else {
+            is_above_slope(player_transform, player_state.size(), slope_transform, slope)
+        }
+    }
+
+    // Keeps the player on the surface of any slope under it. Vertical velocity follows the
+    // surface, so running off the top of a slope at speed launches the player. Under flipped
+    // gravity the player runs along the slope's flat underside instead.
+    pub fn slope_system(
+        mut player_query: Query<(&mut Transform, &mut Velocity, &PlayerState, &mut SlopeContact), With<Player>>,
+        slope_query: Query<(Entity, &Transform, &Slope), Without<Player>>,
+    ) {
+        let Ok((mut player_transform, mut velocity, player_state, mut contact)) = player_query.get_single_mut() else {
+            return;
+        };
+        contact.slope = None;
+
+        let half_size = player_state.size() / 2.0;
+        let player_x = player_transform.translation.x;
+        let player_bottom = player_transform.translation.y - half_size.y;
+        let player_top = player_transform.translation.y + half_size.y;
+
+        for (slope_entity, slope_transform, slope) in slope_query.iter() {
+            let origin = slope_transform.translation.truncate();
+            let left = player_x - half_size.x - origin.x;
+            let right = player_x + half_size.x - origin.x;
+            if right < 0.0 || left > slope.width {
+                continue;
+            }
+
+            if player_state.gravity_flipped {
+                let support = origin.y;
+                let jumping = velocity.linvel.y < -1.0;
+                if jumping || player_top < support - 1.0 || player_top > support + SLOPE_SNAP_DEPTH {
+                    continue;
+                }
+
+                player_transform.translation.y = support - half_size.y;
+                velocity.linvel.y = 0.0;
+                contact.slope = Some(slope_entity);
+                break;
+            }
+
+            // The player rests on whichever bottom corner is higher up the slope
+            let support = origin.y + slope.surface_height(left).max(slope.surface_height(right));
+            // Follows the player's real horizontal speed, platformer players can stand still or walk back
+            let surface_velocity = velocity.linvel.x * slope.rise / slope.width;
+            let jumping = velocity.linvel.y > surface_velocity.max(0.0) + 1.0;
+            if jumping || player_bottom > support + 1.0 || player_bottom < support - SLOPE_SNAP_DEPTH {
+                continue;
+            }
+
+            player_transform.translation.y = support + half_size.y;
+            velocity.linvel.y = surface_velocity;
+            contact.slope = Some(slope_entity);
+            break;
+        }
+    }
+
     pub fn is_top_collision(player_transform: &Transform, player_size: Vec2, obstacle_transform: &Transform, obstacle_size: Vec2) -> bool {
         let player_bottom = player_transform.translation.y - player_size.y / 2.0;
         let obstacle_top = obstacle_transform.translation.y + obstacle_size.y / 2.0;
         player_bottom > obstacle_top
     }
 
+    pub fn is_bottom_collision(player_transform: &Transform, player_size: Vec2, obstacle_transform: &Transform, obstacle_size: Vec2) -> bool {
+        let player_top = player_transform.translation.y + player_size.y / 2.0;
+        let obstacle_bottom = obstacle_transform.translation.y - obstacle_size.y / 2.0;
+        player_top < obstacle_bottom
+    }
+
+    // Whether the player touched the side of an obstacle it can stand on under the current gravity
+    pub fn is_landing_collision(player_transform: &Transform, player_state: &PlayerState, obstacle_transform: &Transform, obstacle_size: Vec2) -> bool {
+        if player_state.gravity_flipped {
+            is_bottom_collision(player_transform, player_state.size(), obstacle_transform, obstacle_size)
+        } else {
+            is_top_collision(player_transform, player_state.size(), obstacle_transform, obstacle_size)
+        }
+    }
+
     pub fn cleanup_gameplay(
         mut commands: Commands,
+        mut rapier_config: ResMut<RapierConfiguration>,
         entities: Query<Entity, (Without<Camera>, Without<Window>)>,
         camera_entities: Query<Entity, With<GameplayCamera>>,
     ) {
         debug!("Cleaning up gameplay entities...");
+        commands.insert_resource(ClearColor::default());
+        // A death sequence leaves physics frozen
+        rapier_config.physics_pipeline_active = true;
+        commands.remove_resource::<LevelEnd>();
+        commands.remove_resource::<PlayerDeath>();
         for entity in entities.iter() {
             commands.entity(entity).despawn_recursive();
             debug!("Entity {:?} despawned.", entity);
@@ -199,62 +846,442 @@ pub mod gameplay {
         }
     }
 
+    // How long a held robot jump keeps rising
+    const ROBOT_BOOST_TIME: f32 = 0.25;
+    // Farthest a spider teleport searches for the opposite surface
+    const SPIDER_RANGE: f32 = 1000.0;
+
+    #[allow(clippy::type_complexity)]
     pub fn player_movement_system(
+        mut commands: Commands,
         keyboard_input: Res<ButtonInput<KeyCode>>,
-        mut query: Query<&mut Velocity, With<Player>>,
+        time: Res<Time>,
+        platformer_settings: Res<PlatformerSettings>,
+        rapier_context: Res<RapierContext>,
+        mut query: Query<(Entity, &mut Transform, &mut Velocity, &mut GravityScale, &mut PlayerState, &mut JumpBuffer, &mut RobotBoost, &SlopeContact), With<Player>>,
     ) {
-        for mut velocity in &mut query {
-            velocity.linvel.x = 200.0;
+        for (entity, mut transform, mut velocity, mut gravity_scale, mut player_state, mut jump_buffer, mut robot_boost, slope_contact) in &mut query {
+            if player_state.platformer {
+                let mut direction = 0.0;
+                if keyboard_input.pressed(KeyCode::ArrowLeft) || keyboard_input.pressed(KeyCode::KeyA) {
+                    direction -= 1.0;
+                }
+                if keyboard_input.pressed(KeyCode::ArrowRight) || keyboard_input.pressed(KeyCode::KeyD) {
+                    direction += 1.0;
+                }
+                velocity.linvel.x = platformer_velocity(velocity.linvel.x, direction, player_state.speed, &platformer_settings, time.delta_seconds());
+            } else {
+                velocity.linvel.x = player_state.speed;
+            }
             debug!("Player horizontal: {}", velocity.linvel.x);
             // debug!("Abs: {}", velocity.linvel.y.abs());
 
-            if keyboard_input.pressed(KeyCode::Space) || keyboard_input.pressed(KeyCode::ArrowUp) {
-                if velocity.linvel.y.abs() < 0.001 {
-                    velocity.linvel.y = 300.0;
-                    debug!("Player vertical velocity set to 300.0.");
+            let pressed = keyboard_input.pressed(KeyCode::Space) || keyboard_input.pressed(KeyCode::ArrowUp);
+            let just_pressed = keyboard_input.just_pressed(KeyCode::Space) || keyboard_input.just_pressed(KeyCode::ArrowUp);
+            let on_slope = slope_contact.slope.is_some();
+            let grounded = on_slope || velocity.linvel.y.abs() < 0.001;
+            // Jumping off an upward slope keeps the slope's climb speed
+            let slope_velocity = if on_slope { velocity.linvel.y.max(0.0) } else { 0.0 };
+
+            match player_state.game_mode {
+                GameMode::Cube => {
+                    if jump_buffer.update(time.delta(), pressed, grounded) {
+                        velocity.linvel.y = player_state.jump_velocity() + slope_velocity;
+                        debug!("Player vertical velocity set to {}.", velocity.linvel.y);
+                    }
+                }
+                GameMode::Robot => {
+                    if jump_buffer.update(time.delta(), pressed, grounded) {
+                        velocity.linvel.y = player_state.jump_velocity() + slope_velocity;
+                        robot_boost.remaining = ROBOT_BOOST_TIME;
+                    } else if pressed && robot_boost.remaining > 0.0 {
+                        // Keep rising for as long as jump is held, up to the boost time
+                        robot_boost.remaining -= time.delta_seconds();
+                        velocity.linvel.y = player_state.jump_velocity();
+                    } else {
+                        robot_boost.remaining = 0.0;
+                    }
+                }
+                GameMode::Spider => {
+                    // Holding jump would bounce between surfaces, so only presses count
+                    if jump_buffer.update(time.delta(), just_pressed, grounded) {
+                        spider_teleport(&mut commands, &rapier_context, entity, &mut transform, &mut velocity, &mut player_state);
+                        gravity_scale.0 = player_state.gravity_scale();
+                    }
+                }
+                GameMode::Swing => {
+                    if just_pressed {
+                        player_state.gravity_flipped = !player_state.gravity_flipped;
+                        gravity_scale.0 = player_state.gravity_scale();
+                        debug!("Swing flipped gravity, flipped: {}.", player_state.gravity_flipped);
+                    }
                 }
             }
         }
     }
 
+    // Moves the player straight to the surface on the other side and flips gravity.
+    // Nothing happens when there is no surface to land on.
+    fn spider_teleport(
+        commands: &mut Commands,
+        rapier_context: &RapierContext,
+        player: Entity,
+        transform: &mut Transform,
+        velocity: &mut Velocity,
+        player_state: &mut PlayerState,
+    ) {
+        let half_size = player_state.size() / 2.0;
+        let direction = if player_state.gravity_flipped { -1.0 } else { 1.0 };
+        let origin = transform.translation.truncate();
+        let filter = QueryFilter::new().exclude_sensors().exclude_rigid_body(player);
+        let Some((_, distance)) = rapier_context.cast_ray(origin, Vec2::new(0.0, direction), SPIDER_RANGE, true, filter) else {
+            return;
+        };
+
+        let from = transform.translation;
+        transform.translation.y += direction * (distance - half_size.y).max(0.0);
+        velocity.linvel.y = 0.0;
+        player_state.gravity_flipped = !player_state.gravity_flipped;
+
+        // Streak covering the distance the player jumped
+        let length = (transform.translation.y - from.y).abs() + half_size.y * 2.0;
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: player_state.game_mode.color().with_alpha(0.6),
+                    custom_size: Some(Vec2::new(half_size.x, length)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(from.x, (from.y + transform.translation.y) / 2.0, -0.1),
+                ..Default::default()
+            },
+            SpiderStreak { timer: Timer::from_seconds(0.2, TimerMode::Once) },
+        ));
+    }
+
+    pub fn spider_streak_system(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut query: Query<(Entity, &mut SpiderStreak, &mut Sprite)>,
+    ) {
+        for (entity, mut streak, mut sprite) in query.iter_mut() {
+            streak.timer.tick(time.delta());
+            if streak.timer.finished() {
+                commands.entity(entity).despawn_recursive();
+            } else {
+                sprite.color.set_alpha(0.6 * (1.0 - streak.timer.fraction()));
+            }
+        }
+    }
+
+    // Starts a dash when jump is pressed on a dash orb. The dash holds its velocity against
+    // gravity until jump is released, then normal physics take over from that velocity.
+    #[allow(clippy::type_complexity)]
+    pub fn dash_orb_system(
+        mut collision_events: EventReader<CollisionEvent>,
+        keyboard_input: Res<ButtonInput<KeyCode>>,
+        orb_query: Query<&DashOrb>,
+        mut player_query: Query<(Entity, &mut Velocity, &mut GravityScale, &PlayerState, &mut OrbContact, &mut Dash), With<Player>>,
+    ) {
+        let Ok((player_entity, mut velocity, mut gravity_scale, player_state, mut contact, mut dash)) = player_query.get_single_mut() else {
+            return;
+        };
+
+        for event in collision_events.read() {
+            let (e1, e2, started) = match event {
+                CollisionEvent::Started(e1, e2, _) => (*e1, *e2, true),
+                CollisionEvent::Stopped(e1, e2, _) => (*e1, *e2, false),
+            };
+            let orb_entity = if e1 == player_entity {
+                e2
+            } else if e2 == player_entity {
+                e1
+            } else {
+                continue;
+            };
+            if !orb_query.contains(orb_entity) {
+                continue;
+            }
+            if started {
+                contact.orbs.push(orb_entity);
+            } else {
+                contact.orbs.retain(|&orb| orb != orb_entity);
+            }
+        }
+
+        let pressed = keyboard_input.pressed(KeyCode::Space) || keyboard_input.pressed(KeyCode::ArrowUp);
+        let just_pressed = keyboard_input.just_pressed(KeyCode::Space) || keyboard_input.just_pressed(KeyCode::ArrowUp);
+
+        if let Some(dash_velocity) = dash.velocity {
+            if pressed {
+                velocity.linvel = dash_velocity;
+            } else {
+                dash.velocity = None;
+                gravity_scale.0 = player_state.gravity_scale();
+                debug!("Dash ended.");
+            }
+        } else if just_pressed && !contact.orbs.is_empty() {
+            // Each touch of an orb gives one dash
+            let orb_entity = contact.orbs.remove(0);
+            let Ok(orb) = orb_query.get(orb_entity) else {
+                return;
+            };
+            let angle = orb.angle.clamp(-80.0, 80.0).to_radians();
+            let speed = orb.speed.unwrap_or(player_state.speed);
+            // Platformer players dash the way they are moving
+            let facing = if velocity.linvel.x < 0.0 { -1.0 } else { 1.0 };
+            let vertical = if player_state.gravity_flipped { -angle.sin() } else { angle.sin() };
+            let dash_velocity = Vec2::new(facing * angle.cos(), vertical) * speed;
+
+            dash.velocity = Some(dash_velocity);
+            velocity.linvel = dash_velocity;
+            gravity_scale.0 = 0.0;
+            debug!("Dash started with velocity {:?}.", dash_velocity);
+        }
+    }
+
+    // Launches the player upwards, relative to gravity, when it touches a jump pad
+    pub fn jump_pad_system(
+        mut collision_events: EventReader<CollisionEvent>,
+        pad_query: Query<&JumpPad>,
+        mut player_query: Query<(Entity, &mut Velocity, &PlayerState), With<Player>>,
+    ) {
+        let Ok((player_entity, mut velocity, player_state)) = player_query.get_single_mut() else {
+            return;
+        };
+
+        for event in collision_events.read() {
+            let CollisionEvent::Started(e1, e2, _) = event else {
+                continue;
+            };
+            let pad_entity = if *e1 == player_entity {
+                *e2
+            } else if *e2 == player_entity {
+                *e1
+            } else {
+                continue;
+            };
+            if let Ok(pad) = pad_query.get(pad_entity) {
+                velocity.linvel.y = if player_state.gravity_flipped { -pad.velocity } else { pad.velocity };
+                debug!("Jump pad used, vertical velocity set to {}.", velocity.linvel.y);
+            }
+        }
+    }
+
+    // Moves the player from a teleport entrance to its exit, keeping its velocity and its
+    // offset from the portal
+    pub fn teleport_portal_system(
+        mut collision_events: EventReader<CollisionEvent>,
+        portal_query: Query<(&Transform, &TeleportPortal), Without<Player>>,
+        mut player_query: Query<(Entity, &mut Transform), With<Player>>,
+        mut teleported: EventWriter<PlayerTeleported>,
+    ) {
+        let Ok((player_entity, mut player_transform)) = player_query.get_single_mut() else {
+            return;
+        };
+
+        for event in collision_events.read() {
+            let CollisionEvent::Started(e1, e2, _) = event else {
+                continue;
+            };
+            let portal_entity = if *e1 == player_entity {
+                *e2
+            } else if *e2 == player_entity {
+                *e1
+            } else {
+                continue;
+            };
+            let Ok((portal_transform, portal)) = portal_query.get(portal_entity) else {
+                continue;
+            };
+
+            let offset = portal.exit - portal_transform.translation.truncate();
+            player_transform.translation += offset.extend(0.0);
+            teleported.send(PlayerTeleported { offset });
+            info!("Player teleported by {:?}.", offset);
+        }
+    }
+
+    // Switches the player's game mode, hitbox and look when it passes a mode portal
+    pub fn portal_system(
+        mut collision_events: EventReader<CollisionEvent>,
+        portal_query: Query<&GameModePortal>,
+        mut player_query: Query<(Entity, &mut PlayerState, &mut Collider, &mut RobotBoost, &Children), With<Player>>,
+        mut visual_query: Query<&mut Sprite, With<PlayerVisual>>,
+    ) {
+        for event in collision_events.read() {
+            let CollisionEvent::Started(e1, e2, _) = event else {
+                continue;
+            };
+            let Ok((player_entity, mut player_state, mut collider, mut robot_boost, children)) = player_query.get_single_mut() else {
+                return;
+            };
+            let portal_entity = if *e1 == player_entity {
+                *e2
+            } else if *e2 == player_entity {
+                *e1
+            } else {
+                continue;
+            };
+            let Ok(GameModePortal(mode)) = portal_query.get(portal_entity) else {
+                continue;
+            };
+            if player_state.game_mode == *mode {
+                continue;
+            }
+
+            info!("Player switched to {:?} mode.", mode);
+            player_state.game_mode = *mode;
+            robot_boost.remaining = 0.0;
+            let size = player_state.size();
+            *collider = Collider::cuboid(size.x / 2.0, size.y / 2.0);
+            for &child in children.iter() {
+                if let Ok(mut sprite) = visual_query.get_mut(child) {
+                    sprite.color = mode.color();
+                    sprite.custom_size = Some(size);
+                }
+            }
+        }
+    }
+
+    // Animates the player sprite in the style of its game mode
+    pub fn player_animation_system(
+        time: Res<Time>,
+        player_query: Query<(&Velocity, &PlayerState, &SlopeContact, &Children), With<Player>>,
+        mut visual_query: Query<(&mut Transform, &mut Sprite), With<PlayerVisual>>,
+    ) {
+        for (velocity, player_state, slope_contact, children) in player_query.iter() {
+            let grounded = slope_contact.slope.is_some() || velocity.linvel.y.abs() < 0.001;
+            let gravity_sign = if player_state.gravity_flipped { -1.0 } else { 1.0 };
+
+            for &child in children.iter() {
+                let Ok((mut transform, mut sprite)) = visual_query.get_mut(child) else {
+                    continue;
+                };
+                sprite.flip_y = player_state.gravity_flipped;
+                transform.translation = Vec3::ZERO;
+                transform.scale = Vec3::ONE;
+
+                match player_state.game_mode {
+                    GameMode::Cube => {
+                        if grounded {
+                            // Land flat on whichever side is closest
+                            let (_, _, angle) = transform.rotation.to_euler(EulerRot::XYZ);
+                            let quarter = std::f32::consts::FRAC_PI_2;
+                            transform.rotation = Quat::from_rotation_z((angle / quarter).round() * quarter);
+                        } else {
+                            transform.rotate_z(-gravity_sign * std::f32::consts::PI * 1.5 * time.delta_seconds());
+                        }
+                    }
+                    GameMode::Robot => {
+                        transform.rotation = Quat::IDENTITY;
+                        if grounded {
+                            // Running bob
+                            transform.translation.y = (time.elapsed_seconds() * 18.0).sin().abs() * 2.0 * gravity_sign;
+                        } else {
+                            transform.scale = Vec3::new(0.9, 1.1, 1.0);
+                        }
+                    }
+                    GameMode::Spider => {
+                        transform.rotation = Quat::IDENTITY;
+                        if grounded {
+                            transform.scale = Vec3::new(1.1, 0.85, 1.0);
+                        }
+                    }
+                    GameMode::Swing => {
+                        // Tilt into the direction of travel
+                        transform.rotation = Quat::from_rotation_z((velocity.linvel.y / 800.0).clamp(-0.6, 0.6));
+                    }
+                }
+            }
+        }
+    }
+
+    // Accelerates towards the held direction, or decelerates to a stop with no input
+    pub fn platformer_velocity(current: f32, direction: f32, top_speed: f32, settings: &PlatformerSettings, delta: f32) -> f32 {
+        let target = direction * top_speed;
+        // Turning around brakes as hard as letting go
+        let rate = if direction == 0.0 || current * direction < 0.0 {
+            settings.deceleration
+        } else {
+            settings.acceleration
+        };
+        let step = rate * delta;
+        if (target - current).abs() <= step {
+            target
+        } else {
+            current + step * (target - current).signum()
+        }
+    }
+
+    // Restarting passes through its own state, so the level is cleaned up and set up again
+    pub fn restart_level_system(mut next_state: ResMut<NextState<GameState>>) {
+        next_state.set(GameState::Playing);
+    }
+
     pub fn exit_level_system(
         keyboard_input: Res<ButtonInput<KeyCode>>,
+        playtest: Option<Res<PlaytestLevel>>,
         mut next_state: ResMut<NextState<GameState>>
     ) {
-        if keyboard_input.just_pressed(KeyCode::Escape) {
+        if keyboard_input.just_pressed(KeyCode::Escape) && playtest.is_some() {
+            info!("Playtest stopped. Returning to the editor.");
+            next_state.set(GameState::Editor);
+        } else if keyboard_input.just_pressed(KeyCode::Escape) {
             info!("Player exited the level. Returning to Level Selection.");
             next_state.set(GameState::LevelSelection);
         }
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn level_scrolling_system(
         mut param_set: ParamSet<(
-            Query<&Transform, With<Player>>,
+            Query<(&Transform, &Velocity, &PlayerState), With<Player>>,
             Query<&mut Transform, With<Obstacle>>,
             Query<&mut Transform, With<GameplayCamera>>,
         )>,
+        mut teleported: EventReader<PlayerTeleported>,
+        time: Res<Time>,
     ) {
+        // A teleported camera jumps along with the player, so smoothing carries on undisturbed
+        let teleport_x = teleported.read().map(|event| event.offset.x).sum::<f32>();
+
         // Access the player's transform
-        if let Ok(player_transform) = param_set.p0().get_single() {
+        if let Ok((player_transform, velocity, player_state)) = param_set.p0().get_single() {
             let player_x = player_transform.translation.x;
+            let platformer = player_state.platformer;
+            // Look ahead in whichever direction a platformer player is moving
+            let look_ahead = (velocity.linvel.x / player_state.speed.max(1.0)).clamp(-1.0, 1.0);
+            let smoothing = 1.0 - (-5.0 * time.delta_seconds()).exp();
 
             // Update the camera's position
             for mut camera_transform in param_set.p2().iter_mut() {
-                let target_camera_x = player_x + 100.0; // Offset camera ahead of the player
-                camera_transform.translation.x = target_camera_x;
+                camera_transform.translation.x += teleport_x;
+                if platformer {
+                    let target_camera_x = player_x + 100.0 * look_ahead;
+                    camera_transform.translation.x += (target_camera_x - camera_transform.translation.x) * smoothing;
+                } else {
+                    let target_camera_x = player_x + 100.0; // Offset camera ahead of the player
+                    camera_transform.translation.x = target_camera_x;
+                }
             }
         }
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn continuous_floor_system(
         mut param_set: ParamSet<(
             Query<&mut Transform, With<Floor>>,
-            Query<&Transform, With<Player>>,
+            Query<(&Transform, &PlayerState), With<Player>>,
         )>,
         time: Res<Time>,
     ) {
         // Access the player's position
-        if let Ok(player_transform) = param_set.p1().get_single() {
+        if let Ok((player_transform, player_state)) = param_set.p1().get_single() {
+            // Platformer players can walk back, so the floor has to stay put
+            if player_state.platformer {
+                return;
+            }
             let player_x = player_transform.translation.x;
 
             // Access and modify the floor's position
@@ -299,7 +1326,22 @@ pub mod gameplay {
                 Floor,
                 RigidBody::Fixed,
                 Collider::cuboid(floor_width / 2.0, floor_height / 2.0),
-            ));
+            ))
+            .with_children(|floor| {
+                // Line along the top edge of the ground
+                floor.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::srgb(1.0, 1.0, 1.0),
+                            custom_size: Some(Vec2::new(floor_width, 2.0)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_xyz(0.0, floor_height / 2.0, 0.1),
+                        ..Default::default()
+                    },
+                    GroundLine,
+                ));
+            });
         }
     }
 }
@@ -308,31 +1350,86 @@ pub fn progress_tracker_system(
     player_query: Query<&Transform, With<Player>>,
     finish_query: Query<&Transform, With<FinishLine>>,
     mut progress: ResMut<LevelProgress>,
+    level_end: Option<Res<LevelEnd>>,
+    selected_level: Res<SelectedLevel>,
+    time: Res<Time>,
 ) {
+    // Platformer levels are recorded on completion with their time instead of distance
+    if progress.platformer {
+        // The clock stops when the finish line is touched
+        if player_query.get_single().is_ok() && level_end.is_none() {
+            progress.elapsed_time += time.delta_seconds();
+        }
+        return;
+    }
+
     if let (Ok(player_transform), Ok(finish_transform)) =
         (player_query.get_single(), finish_query.get_single())
     {
-        let start_x: f32 = -200.0;
+        let start_x: f32 = PLAYER_START.x;
         let player_x: f32 = player_transform.translation.x;
         let finish_x: f32 = finish_transform.translation.x;
         let total_distance = (finish_x - start_x).max(1.0); // Prevent division by zero
         let distance_traveled: f32 = (player_x - start_x).max(0.0);
 
         let progress_percentage = (distance_traveled / total_distance) * 100.0;
-        progress.current_percentage = progress_percentage.clamp(0.0, 100.0); // **Fix applied here**
+        // Teleports can move the player back, progress within a run never goes down
+        progress.current_percentage = progress.current_percentage.max(progress_percentage.clamp(0.0, 100.0)); // **Fix applied here**
+
+        // Runs started from a start position are practice and never recorded
+        if progress.from_start_position {
+            return;
+        }
 
         // Save progress to leaderboard
-        let file_path = "assets/progress.json";
+        let file_path = ProgressHistory::FILE_PATH;
         let mut history = ProgressHistory::load(file_path).unwrap_or_else(|_| ProgressHistory::default());
         let date = chrono::Local::now().format("%Y-%m-%d").to_string();
         let new_entry = ProgressEntry {
+            level_id: Some(selected_level.level_id),
             date,
             percentage: progress.current_percentage, // Ensure clamped value is saved
+            time: None,
         };
         history.add_entry(new_entry, file_path);
     }
 }
 
+// Runs on contact with the finish line, before the end sequence plays
+pub fn record_platformer_completion(selected_level: Res<SelectedLevel>, progress: Res<LevelProgress>) {
+    if !progress.platformer || progress.from_start_position {
+        return;
+    }
+
+    let file_path = ProgressHistory::FILE_PATH;
+    let mut history = ProgressHistory::load(file_path).unwrap_or_else(|_| ProgressHistory::default());
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let new_entry = ProgressEntry {
+        level_id: Some(selected_level.level_id),
+        date,
+        percentage: 100.0,
+        time: Some(progress.elapsed_time),
+    };
+    history.add_entry(new_entry, file_path);
+    info!("Platformer level completed in {:.2}s.", progress.elapsed_time);
+}
+
+// Coins only count once the level has been completed in the same run
+pub fn record_collected_coins(
+    selected_level: Res<SelectedLevel>,
+    progress: Res<LevelProgress>,
+    run_coins: Res<RunCoins>,
+) {
+    if progress.from_start_position || run_coins.collected.is_empty() {
+        return;
+    }
+
+    let file_path = CoinRecord::FILE_PATH;
+    let mut record = CoinRecord::load(file_path).unwrap_or_default();
+    record.add_coins(selected_level.level_id, &run_coins.collected, file_path);
+    info!("Recorded coins {:?} for level {}.", run_coins.collected, selected_level.level_id);
+}
+
 pub fn setup_audio_system(mut commands: Commands, asset_server: Res<AssetServer>) {
     let death_sound = asset_server.load("audio/death.ogg");
     let victory_sound = asset_server.load("audio/victory.ogg");
@@ -365,32 +1462,155 @@ pub fn play_sound(commands: &mut Commands, sound: Handle<AudioSource>) {
     });
 }
 
-pub fn setup_leaderboard(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-) {
-    let file_path = "assets/progress.json";
-    let history = ProgressHistory::load(file_path);
+// Best runs across all levels, platformer levels list their completion time
+pub fn leaderboard_text() -> String {
+    let file_path = ProgressHistory::FILE_PATH;
 
-    let mut leaderboard_text = "Leaderboard:\n".to_string();
+    let mut leaderboard_text = String::new();
 
     if let Ok(history) = ProgressHistory::load(file_path) {
         for entry in history.entries.iter().take(5) {
-            leaderboard_text.push_str(&format!("{}: {:.1}%\n", entry.date, entry.percentage));
+            // Entries saved before runs were tagged with their level have no level to show
+            let level = entry.level_id.map(|id| format!("Level {} - ", id)).unwrap_or_default();
+            match entry.time {
+                Some(time) => leaderboard_text.push_str(&format!("{}{}: {:.2}s\n", level, entry.date, time)),
+                None => leaderboard_text.push_str(&format!("{}{}: {:.1}%\n", level, entry.date, entry.percentage)),
+            }
         }
     } else {
         warn!("Failed to load progress history. No leaderboard data available.");
     }
 
-    commands.spawn((
-        TextBundle::from_section(
-            leaderboard_text,
-            TextStyle {
-                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 20.0,
-                color: Color::WHITE,
-            },
-        ),
-    ));
+    if leaderboard_text.is_empty() {
+        leaderboard_text.push_str("No runs recorded yet.");
+    }
+    leaderboard_text
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_rapier2d::rapier::geometry::CollisionEventFlags;
+
+    fn world() -> (World, Entity) {
+        let mut world = World::new();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<LevelProgress>();
+        world.init_resource::<DeathSettings>();
+        let player = world.spawn((Player, Transform::default(), PlayerState::default())).id();
+        (world, player)
+    }
+
+    #[test]
+    fn collision_blocks_overlapping_obstacles_do_not_kill_the_player() {
+        let (mut world, _) = world();
+        let block = world.spawn((CollisionBlock { id: 1 }, Transform::from_xyz(500.0, 0.0, 0.0))).id();
+        let obstacle = world.spawn((Obstacle, Transform::from_xyz(500.0, 0.0, 0.0))).id();
+        let finish = world.spawn((Obstacle, FinishLine, Transform::from_xyz(520.0, 0.0, 0.0))).id();
+        world.send_event(CollisionEvent::Started(block, obstacle, CollisionEventFlags::empty()));
+        world.send_event(CollisionEvent::Started(finish, block, CollisionEventFlags::empty()));
+
+        world.run_system_once(gameplay::collision_event_system);
+
+        assert!(!world.contains_resource::<PlayerDeath>());
+        assert!(!world.contains_resource::<LevelEnd>());
+    }
+
+    #[test]
+    fn lethal_obstacles_touching_the_player_kill_it() {
+        let (mut world, player) = world();
+        let obstacle = world.spawn((Obstacle, Transform::from_xyz(10.0, 0.0, 0.0))).id();
+        world.send_event(CollisionEvent::Started(obstacle, player, CollisionEventFlags::empty()));
+
+        world.run_system_once(gameplay::collision_event_system);
+
+        assert!(world.contains_resource::<PlayerDeath>());
+    }
+
+    // A platformer player standing on a 100 wide slope rising by 50, at its middle
+    fn player_on_slope(linvel_x: f32) -> (World, Entity) {
+        let mut world = World::new();
+        let player_state = PlayerState { platformer: true, ..default() };
+        let half_size = player_state.size() / 2.0;
+        let slope = Slope { width: 100.0, rise: 50.0 };
+        let support = slope.surface_height(50.0 + half_size.x);
+        world.spawn((Transform::default(), slope));
+        let player = world
+            .spawn((
+                Player,
+                Transform::from_xyz(50.0, support + half_size.y, 0.0),
+                Velocity::linear(Vec2::new(linvel_x, 0.0)),
+                player_state,
+                SlopeContact::default(),
+            ))
+            .id();
+        (world, player)
+    }
+
+    #[test]
+    fn platformer_players_at_rest_stay_put_on_slopes() {
+        let (mut world, player) = player_on_slope(0.0);
+
+        world.run_system_once(gameplay::slope_system);
+
+        assert_eq!(world.get::<Velocity>(player).unwrap().linvel.y, 0.0);
+        assert!(world.get::<SlopeContact>(player).unwrap().slope.is_some());
+    }
+
+    #[test]
+    fn platformer_players_walking_left_go_down_rising_slopes() {
+        let (mut world, player) = player_on_slope(-100.0);
+
+        world.run_system_once(gameplay::slope_system);
+
+        assert_eq!(world.get::<Velocity>(player).unwrap().linvel.y, -50.0);
+        assert!(world.get::<SlopeContact>(player).unwrap().slope.is_some());
+    }
+
+    #[test]
+    fn flipped_players_run_along_the_underside_of_slopes() {
+        let mut world = World::new();
+        let player_state = PlayerState { gravity_flipped: true, ..default() };
+        let half_size = player_state.size() / 2.0;
+        world.spawn((Transform::from_xyz(0.0, 100.0, 0.0), Slope { width: 100.0, rise: 50.0 }));
+        // Falling up, a little into the slope
+        let player = world
+            .spawn((
+                Player,
+                Transform::from_xyz(50.0, 100.0 - half_size.y + 4.0, 0.0),
+                Velocity::linear(Vec2::new(200.0, 120.0)),
+                player_state,
+                SlopeContact::default(),
+            ))
+            .id();
+
+        world.run_system_once(gameplay::slope_system);
+
+        assert_eq!(world.get::<Transform>(player).unwrap().translation.y, 100.0 - half_size.y);
+        assert_eq!(world.get::<Velocity>(player).unwrap().linvel.y, 0.0);
+        assert!(world.get::<SlopeContact>(player).unwrap().slope.is_some());
+        let slope_transform = Transform::from_xyz(0.0, 100.0, 0.0);
+        let slope = Slope { width: 100.0, rise: 50.0 };
+        assert!(gameplay::is_landing_on_slope(world.get::<Transform>(player).unwrap(), &player_state, &slope_transform, &slope));
+    }
+
+    fn pad_contact(gravity_flipped: bool) -> f32 {
+        let (mut world, player) = world();
+        world.get_mut::<PlayerState>(player).unwrap().gravity_flipped = gravity_flipped;
+        world.entity_mut(player).insert(Velocity::linear(Vec2::new(200.0, -80.0)));
+        let pad = world.spawn(JumpPad { velocity: 450.0 }).id();
+        world.send_event(CollisionEvent::Started(pad, player, CollisionEventFlags::empty()));
+
+        world.run_system_once(gameplay::jump_pad_system);
+
+        world.get::<Velocity>(player).unwrap().linvel.y
+    }
+
+    #[test]
+    fn jump_pads_launch_the_player_against_gravity() {
+        assert_eq!(pad_contact(false), 450.0);
+        assert_eq!(pad_contact(true), -450.0);
+    }
+}
@@ -0,0 +1,433 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use crate::components::{BlendingColorMaterial, CollisionBlock, ColorChannels, Floor, GroundLine, GroupMember, LevelClock, PendingSpawn, Player, PlayerDeath, PlayerState, Trigger, TriggerDispatch};
+use crate::levels::{TriggerAction, TriggerActivation, BACKGROUND_CHANNEL, GROUND_CHANNEL, LINE_CHANNEL};
+use crate::states::GameState;
+
+#[derive(Default)]
+pub struct TriggerPlugin;
+
+impl Plugin for TriggerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LevelClock>()
+            .add_systems(
+                Update,
+                (
+                    // The level clock stops while the player dies, freezing every animation
+                    level_clock_system.run_if(not(resource_exists::<PlayerDeath>)),
+                    trigger_dispatch_system,
+                    group_animation_system,
+                    color_channel_system,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+pub fn level_clock_system(time: Res<Time>, mut clock: ResMut<LevelClock>) {
+    clock.elapsed += time.delta_seconds();
+}
+
+// Upper bound on trigger firings handled in one frame, guards against zero-delay spawn loops
+const MAX_FIRES_PER_FRAME: usize = 1000;
+
+// Fires triggers activated by the player, by collision blocks or by due spawns and applies
+// their immediate effects. Triggers fire in (time, index) order, so a run replays identically.
+#[allow(clippy::too_many_arguments)]
+pub fn trigger_dispatch_system(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    clock: Res<LevelClock>,
+    dispatch: Option<ResMut<TriggerDispatch>>,
+    player_query: Query<(&Transform, &PlayerState), With<Player>>,
+    block_query: Query<&CollisionBlock>,
+    mut trigger_query: Query<(Entity, &mut Trigger)>,
+    mut member_query: Query<(Entity, &GroupMember, &mut Visibility)>,
+) {
+    let Some(mut dispatch) = dispatch else {
+        return;
+    };
+    let mut queue = Vec::new();
+
+    // Overlaps between collision blocks, the player plays no part in these. Read before the
+    // player check so events from frames without a player don't fire triggers later.
+    for event in collision_events.read() {
+        let (e1, e2, started) = match event {
+            CollisionEvent::Started(e1, e2, _) => (*e1, *e2, true),
+            CollisionEvent::Stopped(e1, e2, _) => (*e1, *e2, false),
+        };
+        let (Ok(block_a), Ok(block_b)) = (block_query.get(e1), block_query.get(e2)) else {
+            continue;
+        };
+        for (entity, trigger) in trigger_query.iter() {
+            if trigger.data.activation() != TriggerActivation::Collision {
+                continue;
+            }
+            if trigger.data.collision.is_some_and(|condition| condition.matches(block_a.id, block_b.id, started)) {
+                queue.push((clock.elapsed, trigger.index, entity));
+            }
+        }
+    }
+
+    let Ok((player_transform, player_state)) = player_query.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation.truncate();
+    let player_half_size = player_state.size() / 2.0;
+
+    // Triggers the player activated this frame
+    for (entity, trigger) in trigger_query.iter() {
+        if !trigger.fire_times.is_empty() {
+            continue;
+        }
+        let data = &trigger.data;
+        match data.activation() {
+            TriggerActivation::Position if player_position.x >= data.position.x => {
+                // Back-date the fire time to the moment the player actually crossed the
+                // trigger, so the result does not depend on the frame rate
+                let time = if data.position.x < dispatch.start_x {
+                    f32::NEG_INFINITY
+                } else {
+                    clock.elapsed - (player_position.x - data.position.x) / player_state.speed.max(1.0)
+                };
+                queue.push((time, trigger.index, entity));
+            }
+            TriggerActivation::Touch => {
+                let half_size = data.size.unwrap_or(Vec2::splat(30.0)) / 2.0;
+                let distance = (player_position - data.position).abs();
+                if distance.x <= half_size.x + player_half_size.x && distance.y <= half_size.y + player_half_size.y {
+                    queue.push((clock.elapsed, trigger.index, entity));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut fires = 0;
+    loop {
+        // Spawns that are due activate the spawn triggers in their group
+        dispatch.pending.sort_by(|a, b| a.time.total_cmp(&b.time).then(a.order.cmp(&b.order)));
+        let due_count = dispatch.pending.iter().take_while(|spawn| spawn.time <= clock.elapsed).count();
+        for spawn in dispatch.pending.drain(..due_count).collect::<Vec<_>>() {
+            for (entity, trigger) in trigger_query.iter() {
+                if trigger.data.activation() == TriggerActivation::Spawn && trigger.data.in_group(spawn.group) {
+                    queue.push((spawn.time, trigger.index, entity));
+                }
+            }
+        }
+
+        if queue.is_empty() {
+            break;
+        }
+        queue.sort_by(|a: &(f32, usize, Entity), b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        for (time, _, entity) in std::mem::take(&mut queue) {
+            fires += 1;
+            if fires > MAX_FIRES_PER_FRAME {
+                warn!("More than {} triggers fired in one frame. Dropping pending spawns.", MAX_FIRES_PER_FRAME);
+                dispatch.pending.clear();
+                return;
+            }
+
+            let Ok((_, mut trigger)) = trigger_query.get_mut(entity) else {
+                continue;
+            };
+            trigger.fire_times.push(time);
+            debug!("Trigger {} fired at {:.3}s: {:?}", trigger.index, time, trigger.data.action);
+
+            // Move, rotate, scale and color triggers are animated from their fire times
+            match trigger.data.action.clone() {
+                TriggerAction::Toggle { group, enabled } => {
+                    for (member_entity, member, mut visibility) in member_query.iter_mut() {
+                        if !member.groups.contains(&group) {
+                            continue;
+                        }
+                        if enabled {
+                            *visibility = Visibility::Inherited;
+                            commands.entity(member_entity).remove::<ColliderDisabled>();
+                        } else {
+                            *visibility = Visibility::Hidden;
+                            commands.entity(member_entity).insert(ColliderDisabled);
+                        }
+                    }
+                }
+                TriggerAction::Spawn { group, delay } => {
+                    schedule_spawn(&mut dispatch, time + delay.max(0.0), group);
+                }
+                TriggerAction::Pickup { item, amount } => {
+                    *dispatch.items.entry(item).or_default() += amount;
+                    check_counts(&mut dispatch, &trigger_query, time);
+                }
+                TriggerAction::Count { .. } => {
+                    dispatch.armed_counts.push(entity);
+                    check_counts(&mut dispatch, &trigger_query, time);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn schedule_spawn(dispatch: &mut TriggerDispatch, time: f32, group: u32) {
+    let order = dispatch.next_order;
+    dispatch.next_order += 1;
+    dispatch.pending.push(PendingSpawn { time, group, order });
+}
+
+// Spawns the group of every armed count trigger whose item counter reached its target
+fn check_counts(dispatch: &mut TriggerDispatch, trigger_query: &Query<(Entity, &mut Trigger)>, time: f32) {
+    let armed = std::mem::take(&mut dispatch.armed_counts);
+    for entity in armed {
+        let Ok((_, trigger)) = trigger_query.get(entity) else {
+            continue;
+        };
+        if let TriggerAction::Count { item, target, group } = trigger.data.action {
+            if dispatch.items.get(&item).copied().unwrap_or(0) == target {
+                schedule_spawn(dispatch, time, group);
+                continue;
+            }
+        }
+        dispatch.armed_counts.push(entity);
+    }
+}
+
+// Every time any trigger fired, as (time, trigger index, action)
+fn fired_actions<'a>(triggers: impl Iterator<Item = &'a Trigger>) -> impl Iterator<Item = (f32, usize, &'a TriggerAction)> {
+    triggers.flat_map(|trigger| {
+        trigger.fire_times.iter().map(move |&time| (time, trigger.index, &trigger.data.action))
+    })
+}
+
+// Recomputes every grouped object's transform from its spawn transform and the fired
+// triggers, so the result only depends on the level clock
+pub fn group_animation_system(
+    clock: Res<LevelClock>,
+    trigger_query: Query<&Trigger>,
+    mut member_query: Query<(&GroupMember, &mut Transform)>,
+) {
+    let mut fired = fired_actions(trigger_query.iter())
+        .filter(|(_, _, action)| matches!(action, TriggerAction::Move { .. } | TriggerAction::Rotate { .. } | TriggerAction::Scale { .. }))
+        .collect::<Vec<_>>();
+    if fired.is_empty() {
+        return;
+    }
+    fired.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    for (member, mut transform) in member_query.iter_mut() {
+        let mut animated = member.base;
+        for &(fired_at, _, action) in fired.iter() {
+            let duration = action.duration();
+            let linear = if duration > 0.0 { (clock.elapsed - fired_at) / duration } else { 1.0 };
+            let t = action.easing().apply(linear);
+
+            match action {
+                TriggerAction::Move { group, offset, .. } if member.groups.contains(group) => {
+                    animated.translation += (*offset * t).extend(0.0);
+                }
+                TriggerAction::Rotate { group, degrees, .. } if member.groups.contains(group) => {
+                    animated.rotate_z(degrees.to_radians() * t);
+                }
+                TriggerAction::Scale { group, scale, .. } if member.groups.contains(group) => {
+                    animated.scale *= Vec3::ONE.lerp(scale.extend(1.0), t);
+                }
+                _ => {}
+            }
+        }
+
+        if *transform != animated {
+            *transform = animated;
+        }
+    }
+}
+
+// Fades color channels towards the targets of fired color triggers and pushes the
+// result into every material, sprite and clear color that uses the channel
+#[allow(clippy::too_many_arguments)]
+pub fn color_channel_system(
+    clock: Res<LevelClock>,
+    trigger_query: Query<&Trigger>,
+    color_channels: Option<ResMut<ColorChannels>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut blending_materials: ResMut<Assets<BlendingColorMaterial>>,
+    mut clear_color: ResMut<ClearColor>,
+    mut floor_query: Query<&mut Sprite, (With<Floor>, Without<GroundLine>)>,
+    mut line_query: Query<&mut Sprite, (With<GroundLine>, Without<Floor>)>,
+) {
+    let Some(mut color_channels) = color_channels else {
+        return;
+    };
+    // The ground and background still need their starting color on the first frame
+    let force_update = color_channels.is_added();
+
+    let mut fired = fired_actions(trigger_query.iter())
+        .filter(|(_, _, action)| matches!(action, TriggerAction::Color { .. }))
+        .collect::<Vec<_>>();
+    fired.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    for (&id, channel) in color_channels.channels.iter_mut() {
+        let mut color = channel.base;
+        for &(fired_at, _, action) in fired.iter() {
+            if let TriggerAction::Color { channel: target_channel, color: target, alpha, .. } = action {
+                if *target_channel != id {
+                    continue;
+                }
+                let duration = action.duration();
+                let linear = if duration > 0.0 { (clock.elapsed - fired_at) / duration } else { 1.0 };
+                let target = Color::srgba(target[0], target[1], target[2], alpha.unwrap_or(color.alpha()));
+                color = color.mix(&target, action.easing().apply(linear));
+            }
+        }
+
+        if color == channel.current && !force_update {
+            continue;
+        }
+        channel.current = color;
+
+        if let Some(material) = materials.get_mut(&channel.material) {
+            material.color = color;
+        }
+        if let Some(material) = blending_materials.get_mut(&channel.blending_material) {
+            material.color = color.into();
+        }
+        match id {
+            BACKGROUND_CHANNEL => clear_color.0 = color,
+            GROUND_CHANNEL => floor_query.iter_mut().for_each(|mut sprite| sprite.color = color),
+            LINE_CHANNEL => line_query.iter_mut().for_each(|mut sprite| sprite.color = color),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_rapier2d::rapier::geometry::CollisionEventFlags;
+    use crate::levels::{CollisionCondition, TriggerData};
+
+    fn trigger(index: usize, x: f32, action: TriggerAction) -> Trigger {
+        Trigger {
+            index,
+            data: TriggerData {
+                position: Vec2::new(x, 0.0),
+                action,
+                activation: None,
+                groups: None,
+                size: None,
+                collision: None,
+            },
+            fire_times: Vec::new(),
+        }
+    }
+
+    fn toggle(enabled: bool) -> TriggerAction {
+        TriggerAction::Toggle { group: 1, enabled }
+    }
+
+    // A run one second in, with the player 300 units along at the default speed of 200
+    fn world(start_x: f32) -> World {
+        let mut world = World::new();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.insert_resource(LevelClock { elapsed: 1.0 });
+        world.insert_resource(TriggerDispatch { start_x, ..default() });
+        world.spawn((Player, Transform::from_xyz(300.0, 0.0, 0.0), PlayerState::default()));
+        world
+    }
+
+    fn fire_times(world: &mut World) -> Vec<(usize, Vec<f32>)> {
+        let mut fired = world
+            .query::<&Trigger>()
+            .iter(world)
+            .map(|trigger| (trigger.index, trigger.fire_times.clone()))
+            .collect::<Vec<_>>();
+        fired.sort_by_key(|(index, _)| *index);
+        fired
+    }
+
+    #[test]
+    fn position_triggers_crossed_in_one_frame_fire_in_crossing_order() {
+        let mut world = world(0.0);
+        // The lower index is crossed last, so it has to be applied last
+        world.spawn(trigger(0, 200.0, toggle(true)));
+        world.spawn(trigger(1, 100.0, toggle(false)));
+        let member = world.spawn((GroupMember { groups: vec![1], base: Transform::default() }, Visibility::Inherited)).id();
+
+        world.run_system_once(trigger_dispatch_system);
+
+        assert_eq!(fire_times(&mut world), vec![(0, vec![0.5]), (1, vec![0.0])]);
+        assert_eq!(world.get::<Visibility>(member), Some(&Visibility::Inherited));
+    }
+
+    #[test]
+    fn spawns_fire_at_their_back_dated_due_time() {
+        let mut world = world(0.0);
+        world.spawn(trigger(0, 100.0, TriggerAction::Spawn { group: 2, delay: 0.25 }));
+        let mut spawned = trigger(1, 0.0, toggle(true));
+        spawned.data.activation = Some(TriggerActivation::Spawn);
+        spawned.data.groups = Some(vec![2]);
+        world.spawn(spawned);
+
+        world.run_system_once(trigger_dispatch_system);
+
+        assert_eq!(fire_times(&mut world), vec![(0, vec![0.0]), (1, vec![0.25])]);
+    }
+
+    #[test]
+    fn triggers_behind_the_start_position_count_as_fired_before_the_run() {
+        let mut world = world(150.0);
+        world.spawn(trigger(0, 100.0, toggle(true)));
+        world.spawn(trigger(1, 200.0, toggle(true)));
+
+        world.run_system_once(trigger_dispatch_system);
+
+        assert_eq!(fire_times(&mut world), vec![(0, vec![f32::NEG_INFINITY]), (1, vec![0.5])]);
+    }
+
+    #[test]
+    fn a_reset_run_replays_the_same_fire_times() {
+        let mut world = world(0.0);
+        world.spawn(trigger(0, 100.0, TriggerAction::Spawn { group: 2, delay: 0.1 }));
+        let mut spawned = trigger(1, 0.0, TriggerAction::Spawn { group: 2, delay: 0.3 });
+        spawned.data.activation = Some(TriggerActivation::Spawn);
+        spawned.data.groups = Some(vec![2]);
+        world.spawn(spawned);
+
+        world.run_system_once(trigger_dispatch_system);
+        let first_run = fire_times(&mut world);
+
+        // Restarting resets the dispatch state and the triggers, like setup_gameplay does
+        world.insert_resource(LevelClock { elapsed: 1.0 });
+        world.insert_resource(TriggerDispatch::default());
+        for mut trigger in world.query::<&mut Trigger>().iter_mut(&mut world) {
+            trigger.fire_times.clear();
+        }
+        world.run_system_once(trigger_dispatch_system);
+
+        assert_eq!(fire_times(&mut world), first_run);
+        assert!(world.resource::<TriggerDispatch>().pending.iter().all(|spawn| spawn.time > 1.0));
+    }
+
+    #[test]
+    fn collisions_without_a_player_do_not_fire_later() {
+        let mut world = world(0.0);
+        let player = world.query_filtered::<Entity, With<Player>>().single(&world);
+        world.despawn(player);
+        let mut watcher = trigger(0, 0.0, toggle(true));
+        watcher.data.activation = Some(TriggerActivation::Collision);
+        watcher.data.collision = Some(CollisionCondition { block_a: 1, block_b: 2, on_exit: None });
+        world.spawn(watcher);
+        let a = world.spawn(CollisionBlock { id: 1 }).id();
+        let b = world.spawn(CollisionBlock { id: 2 }).id();
+        world.send_event(CollisionEvent::Started(a, b, CollisionEventFlags::empty()));
+
+        // Registered once so the event reader keeps its position between runs
+        let dispatch = world.register_system(trigger_dispatch_system);
+        world.run_system(dispatch).unwrap();
+        world.spawn((Player, Transform::from_xyz(300.0, 0.0, 0.0), PlayerState::default()));
+        world.run_system(dispatch).unwrap();
+
+        assert_eq!(fire_times(&mut world), vec![(0, vec![])]);
+    }
+}
@@ -2,7 +2,9 @@
 use bevy::app::AppExit;
 use bevy::prelude::*;
 
-use crate::components::{BackButton, LevelButton, QuitButton, SelectedLevel, StartButton, UICamera, RestartButton, GameOverText, ReturnToMenuButton, NextLevelButton, LevelProgress, ProgressText, LeaderboardButton, ProgressHistory};
+use crate::components::{BackButton, LevelButton, QuitButton, SelectedLevel, StartButton, UICamera, RestartButton, GameOverText, ReturnToMenuButton, NextLevelButton, LevelProgress, ProgressText, LeaderboardButton, StartPositionButton, CoinRecord, RunCoins, EditorButton, EditorLevelButton, SelectedEditorLevel, ProgressHistory};
+use crate::systems::leaderboard_text;
+use crate::levels::{list_level_ids, load_level};
 use crate::states::GameState;
 
 pub fn setup_title_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -78,7 +80,7 @@ pub fn setup_title_screen(mut commands: Commands, asset_server: Res<AssetServer>
                 });
             debug!("Start button added to title screen.");
 
-            // Edit Level button
+            // Editor button
             parent
                 .spawn((
                     ButtonBundle {
@@ -90,24 +92,22 @@ pub fn setup_title_screen(mut commands: Commands, asset_server: Res<AssetServer>
                             align_items: AlignItems::Center,
                             ..default()
                         },
-                        background_color: BackgroundColor(Color::srgb(0.01, 0.01, 0.01)),
+                        background_color: BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
                         ..default()
                     },
-                    LevelButton { level_id: 0 }, // Special marker for editor
+                    EditorButton,
                 ))
                 .with_children(|button| {
                     button.spawn(TextBundle::from_section(
-                        "Edit Level",
+                        "Editor",
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 40.0,
-                            // color: Color::BLACK,
-                            // gray color
-                            color: Color::srgb(0.217, 0.217, 0.217),
+                            color: Color::BLACK,
                         },
                     ));
                 });
-            debug!("Edit Level button added to title screen.");
+            debug!("Editor button added to title screen.");
 
             // Quit button
             parent
@@ -140,6 +140,7 @@ pub fn setup_title_screen(mut commands: Commands, asset_server: Res<AssetServer>
         });
 }
 
+#[allow(clippy::type_complexity)]
 pub fn button_system(
     mut interaction_query: Query<
         (
@@ -150,13 +151,14 @@ pub fn button_system(
             Option<&LevelButton>,
             Option<&LeaderboardButton>,
             Option<&BackButton>,
+            Option<&EditorButton>,
         ),
         (Changed<Interaction>, With<Button>),
     >,
     mut next_state: ResMut<NextState<GameState>>,
     mut app_exit_events: EventWriter<AppExit>,
 ) {
-    for (interaction, mut color, start_button, quit_button, level_button, leaderboard_button, back_button) in
+    for (interaction, mut color, start_button, quit_button, level_button, leaderboard_button, back_button, editor_button) in
         interaction_query.iter_mut()
     {
         match *interaction {
@@ -179,6 +181,9 @@ pub fn button_system(
                 } else if back_button.is_some() {
                     info!("Back button pressed. Returning to Title Screen.");
                     next_state.set(GameState::TitleScreen);
+                } else if editor_button.is_some() {
+                    info!("Editor button pressed. Transitioning to the editor level picker.");
+                    next_state.set(GameState::EditorLevelSelection);
                 }
             }
             Interaction::Hovered => {
@@ -238,32 +243,88 @@ pub fn setup_level_selection(mut commands: Commands, asset_server: Res<AssetServ
 
             // Level buttons
             let level_count = 5; // Number of levels
+            let coin_record = CoinRecord::load(CoinRecord::FILE_PATH).unwrap_or_default();
             for level_id in 1..=level_count {
+                let level = load_level(level_id);
+                let start_position_count = level.as_ref().map(|level| level.start_positions.len()).unwrap_or(0);
+                let coin_count = level.as_ref().map(|level| level.coins.len()).unwrap_or(0);
+
                 parent
-                    .spawn((
-                        ButtonBundle {
-                            style: Style {
-                                width: Val::Px(300.0),
-                                height: Val::Px(65.0),
-                                margin: UiRect::all(Val::Px(10.0)),
-                                justify_content: JustifyContent::Center,
-                                align_items: AlignItems::Center,
-                                ..default()
-                            },
-                            background_color: BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
                             ..default()
                         },
-                        LevelButton { level_id },
-                    ))
-                    .with_children(|button| {
-                        button.spawn(TextBundle::from_section(
-                            format!("Level {}", level_id),
-                            TextStyle {
-                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                                font_size: 40.0,
-                                color: Color::BLACK,
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(300.0),
+                                    height: Val::Px(65.0),
+                                    margin: UiRect::all(Val::Px(10.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                                ..default()
                             },
-                        ));
+                            LevelButton { level_id },
+                        ))
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section(
+                                format!("Level {}", level_id),
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 40.0,
+                                    color: Color::BLACK,
+                                },
+                            ));
+                        });
+
+                        // Start position picker, only shown for levels that have start positions
+                        if start_position_count > 0 {
+                            row.spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        width: Val::Px(200.0),
+                                        height: Val::Px(65.0),
+                                        margin: UiRect::all(Val::Px(10.0)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    background_color: BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                                    ..default()
+                                },
+                                StartPositionButton { level_id, index: 0, count: start_position_count },
+                            ))
+                            .with_children(|button| {
+                                button.spawn(TextBundle::from_section(
+                                    start_position_label(0),
+                                    TextStyle {
+                                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                        font_size: 24.0,
+                                        color: Color::BLACK,
+                                    },
+                                ));
+                            });
+                        }
+
+                        // Coins collected in completed runs
+                        if coin_count > 0 {
+                            row.spawn(TextBundle::from_section(
+                                coin_summary(coin_record.collected(level_id), coin_count),
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 24.0,
+                                    color: Color::srgb(1.0, 0.85, 0.1),
+                                },
+                            ));
+                        }
                     });
                 debug!("Level {} button added.", level_id);
             }
@@ -298,6 +359,7 @@ pub fn setup_level_selection(mut commands: Commands, asset_server: Res<AssetServ
         });
 }
 
+#[allow(clippy::type_complexity)]
 pub fn level_button_system(
     mut interaction_query: Query<
         (
@@ -308,6 +370,7 @@ pub fn level_button_system(
         ),
         (Changed<Interaction>, With<Button>),
     >,
+    start_position_query: Query<&StartPositionButton>,
     mut next_state: ResMut<NextState<GameState>>,
     mut selected_level: ResMut<SelectedLevel>,
 ) {
@@ -318,6 +381,10 @@ pub fn level_button_system(
                 if let Some(level_button) = level_button {
                     // Store selected level
                     selected_level.level_id = level_button.level_id;
+                    selected_level.start_position = start_position_query
+                        .iter()
+                        .find(|button| button.level_id == level_button.level_id)
+                        .and_then(|button| button.index.checked_sub(1));
                     next_state.set(GameState::Playing);
                 } else if back_button.is_some() {
                     next_state.set(GameState::TitleScreen);
@@ -333,6 +400,187 @@ pub fn level_button_system(
     }
 }
 
+// Shows each coin slot of a level as collected or missing, e.g. "Coins: [x] [ ] [x]"
+fn coin_summary(collected: &[usize], total: usize) -> String {
+    let slots = (0..total)
+        .map(|index| if collected.contains(&index) { "[x]" } else { "[ ]" })
+        .collect::<Vec<_>>();
+    format!("Coins: {}", slots.join(" "))
+}
+
+// Practice runs are not recorded, so the best time can be missing
+fn time_summary(time: f32, best_time: Option<f32>) -> String {
+    match best_time {
+        Some(best) => format!("Time: {:.2}s (Best: {:.2}s)", time, best),
+        None => format!("Time: {:.2}s", time),
+    }
+}
+
+fn start_position_label(index: usize) -> String {
+    if index == 0 {
+        "Start: Beginning".to_string()
+    } else {
+        format!("Start: Position {}", index)
+    }
+}
+
+pub fn start_position_button_system(
+    mut interaction_query: Query<(&Interaction, &mut StartPositionButton, &Children), Changed<Interaction>>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, mut button, children) in interaction_query.iter_mut() {
+        if *interaction == Interaction::Pressed {
+            // Cycle through the beginning and every start position of the level
+            button.index = (button.index + 1) % (button.count + 1);
+            debug!("Level {} start position set to {}.", button.level_id, button.index);
+            for &child in children.iter() {
+                if let Ok(mut text) = text_query.get_mut(child) {
+                    text.sections[0].value = start_position_label(button.index);
+                }
+            }
+        }
+    }
+}
+
+pub fn setup_editor_level_selection(mut commands: Commands, asset_server: Res<AssetServer>) {
+    info!("Setting up editor level selection screen...");
+
+    // Spawn a camera for the UI
+    commands.spawn((
+        Camera2dBundle::default(),
+        UICamera,
+    ));
+
+    // Root node
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: BackgroundColor(Color::srgb(0.1, 0.1, 0.1)),
+            ..default()
+        })
+        .with_children(|parent| {
+            // Title text
+            parent.spawn(TextBundle {
+                text: Text::from_section(
+                    "Open in Editor",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 60.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                style: Style {
+                    margin: UiRect::bottom(Val::Px(50.0)),
+                    ..default()
+                },
+                ..default()
+            });
+
+            // A new level first, then every level on disk
+            let entries = std::iter::once((None, "New Level".to_string()))
+                .chain(list_level_ids().into_iter().map(|level_id| (Some(level_id), format!("Level {}", level_id))));
+            for (level_id, label) in entries {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(300.0),
+                                height: Val::Px(65.0),
+                                margin: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                            ..default()
+                        },
+                        EditorLevelButton { level_id },
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 40.0,
+                                color: Color::BLACK,
+                            },
+                        ));
+                    });
+            }
+
+            // Back button
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(200.0),
+                            height: Val::Px(65.0),
+                            margin: UiRect::all(Val::Px(10.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                        ..default()
+                    },
+                    BackButton,
+                ))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(
+                        "Back",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 40.0,
+                            color: Color::BLACK,
+                        },
+                    ));
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn editor_level_button_system(
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            Option<&EditorLevelButton>,
+            Option<&BackButton>,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut selected_editor_level: ResMut<SelectedEditorLevel>,
+) {
+    for (interaction, mut color, editor_level_button, back_button) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = BackgroundColor(Color::srgb(0.25, 0.25, 0.25));
+                if let Some(editor_level_button) = editor_level_button {
+                    info!("Opening {:?} in the editor.", editor_level_button.level_id);
+                    selected_editor_level.level_id = editor_level_button.level_id;
+                    next_state.set(GameState::Editor);
+                } else if back_button.is_some() {
+                    next_state.set(GameState::TitleScreen);
+                }
+            }
+            Interaction::Hovered => {
+                *color = BackgroundColor(Color::srgb(0.75, 0.75, 0.75));
+            }
+            Interaction::None => {
+                *color = BackgroundColor(Color::srgb(0.5, 0.5, 0.5));
+            }
+        }
+    }
+}
+
 pub fn cleanup_level_selection(
     mut commands: Commands,
     ui_entities: Query<Entity, With<Node>>,
@@ -445,6 +693,7 @@ pub fn setup_game_over_menu(mut commands: Commands, asset_server: Res<AssetServe
         });
 }
 
+#[allow(clippy::type_complexity)]
 pub fn game_over_menu_buttons(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, Option<&RestartButton>, Option<&ReturnToMenuButton>),
@@ -503,8 +752,19 @@ pub fn cleanup_title_screen(
     }
 }
 
-pub fn setup_victory_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub fn setup_victory_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    selected_level: Res<SelectedLevel>,
+    run_coins: Res<RunCoins>,
+    progress: Res<LevelProgress>,
+) {
     info!("Setting up the Victory Screen...");
+    let coin_count = load_level(selected_level.level_id).map(|level| level.coins.len()).unwrap_or(0);
+    // Recorded when the finish line was touched, so the best time includes this run
+    let best_time = ProgressHistory::load(ProgressHistory::FILE_PATH)
+        .ok()
+        .and_then(|history| history.best_time(selected_level.level_id));
 
     // Spawn a UI camera
     commands.spawn((Camera2dBundle::default(), UICamera));
@@ -542,6 +802,46 @@ pub fn setup_victory_screen(mut commands: Commands, asset_server: Res<AssetServe
                 ..default()
             });
 
+            // Completion time of platformer levels
+            if progress.platformer {
+                parent.spawn(TextBundle {
+                    text: Text::from_section(
+                        time_summary(progress.elapsed_time, best_time),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    style: Style {
+                        margin: UiRect::bottom(Val::Px(30.0)),
+                        align_self: AlignSelf::Center,
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+
+            // Coins collected during this run
+            if coin_count > 0 {
+                parent.spawn(TextBundle {
+                    text: Text::from_section(
+                        coin_summary(&run_coins.collected, coin_count),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 40.0,
+                            color: Color::srgb(1.0, 0.85, 0.1),
+                        },
+                    ),
+                    style: Style {
+                        margin: UiRect::bottom(Val::Px(30.0)),
+                        align_self: AlignSelf::Center,
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+
             // Next Level Button
             parent
                 .spawn((
@@ -657,6 +957,7 @@ pub fn setup_victory_screen(mut commands: Commands, asset_server: Res<AssetServe
         });
 }
 
+#[allow(clippy::type_complexity)]
 pub fn victory_screen_buttons(
     mut interaction_query: Query<
         (
@@ -680,6 +981,7 @@ pub fn victory_screen_buttons(
                 if next_level_button.is_some() {
                     info!("Next Level button pressed.");
                     selected_level.level_id += 1; // Move to the next level
+                    selected_level.start_position = None;
                     next_state.set(GameState::Playing);
                 } else if restart_button.is_some() {
                     info!("Restart button pressed. Restarting current level...");
@@ -727,13 +1029,20 @@ pub fn update_progress_ui(
 ) {
     if progress.is_changed() {
         for mut text in query.iter_mut() {
-            text.sections[0].value = format!("Progress: {:.1}%", progress.current_percentage);
+            text.sections[0].value = if progress.platformer {
+                format!("Time: {:.2}s", progress.elapsed_time)
+            } else if progress.from_start_position {
+                format!("Progress: {:.1}% (start position)", progress.current_percentage)
+            } else {
+                format!("Progress: {:.1}%", progress.current_percentage)
+            };
         }
     }
 }
 
 pub fn setup_leaderboard_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
     info!("Setting up the leaderboard screen...");
+    let leaderboard = leaderboard_text();
 
     // Spawn a UI camera
     commands.spawn((Camera2dBundle::default(), UICamera));
@@ -773,7 +1082,7 @@ pub fn setup_leaderboard_screen(mut commands: Commands, asset_server: Res<AssetS
             // Leaderboard text
             parent.spawn(TextBundle {
                 text: Text::from_section(
-                    "Loading...",
+                    leaderboard,
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 30.0,